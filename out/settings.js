@@ -1 +1,3 @@
-window.addEventListener("contextmenu", e => e.preventDefault()); 
\ No newline at end of file
+window.addEventListener("contextmenu", e => e.preventDefault()); 
+// middle click is used for chording, don't start autoscroll
+window.addEventListener("mousedown", e => { if (e.button === 1) e.preventDefault(); });
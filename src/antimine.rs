@@ -0,0 +1,94 @@
+// Anti-mines: a third of the mined cells hold anti-mines instead, which take one off every
+// number around them. Numbers can drop to zero or below with mines next to them, so only a
+// cell with nothing around opens its neighbours.
+//
+// Anti-mines, anti-flags and negative numbers down to -9 have their own sprites, picked by
+// `tile_check` and `game_over`, lower numbers are written over an empty tile. Right clicks go
+// through the flags first and then the anti-flags.
+
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+
+use crate::{board::Board, CurrentBoard, Fonts, TileIndex};
+
+// every this many mined cells one holds anti-mines
+const ANTI_MINE_EVERY: usize = 3;
+
+const NEGATIVE_COLOR: Color = Color::rgb(0.1, 0.2, 0.7);
+
+#[derive(Component)]
+struct NegativeLabel;
+
+pub struct AntiMinePlugin;
+
+impl Plugin for AntiMinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_negative_numbers);
+    }
+}
+
+// Places generated mine positions as mines and anti-mines, whole cells at a time so a cell
+// never holds both.
+pub fn place_with_anti_mines(board: &mut Board, positions: &[(u16, u16)]) {
+    let mut cells: Vec<(u16, u16)> = Vec::new();
+    for position in positions {
+        if !cells.contains(position) {
+            cells.push(*position);
+        }
+    }
+    let anti: Vec<(u16, u16)> = cells.into_iter().skip(ANTI_MINE_EVERY - 1).step_by(ANTI_MINE_EVERY).collect();
+    let (anti, mines): (Vec<_>, Vec<_>) = positions.iter().partition(|position| anti.contains(position));
+    board.place_bombs(&mines);
+    board.place_anti_mines(&anti);
+}
+
+fn draw_negative_numbers(
+    mut commands: Commands,
+    board: Res<CurrentBoard>,
+    index: Res<TileIndex>,
+    fonts: Option<Res<Fonts>>,
+) {
+    if !board.is_changed() {
+        return;
+    }
+    commands.despawn_all::<With<NegativeLabel>>();
+    let Some(fonts) = fonts else { return };
+    if !board.anti_mines() {
+        return;
+    }
+
+    for y in 1..=board.height() {
+        for x in 1..=board.width() {
+            let cell = board.cell(x, y).unwrap();
+            if cell.covered || cell.bomb() || cell.num >= -9 || index.get(x, y).is_none() {
+                continue;
+            }
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        cell.num.to_string(),
+                        TextStyle { font: fonts.main.clone(), font_size: 24.0, color: NEGATIVE_COLOR },
+                    ),
+                    transform: Transform::from_translation(index.center(x, y).extend(0.5)),
+                    ..default()
+                },
+                NegativeLabel,
+                Name::new("Negative number"),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Topology;
+
+    #[test]
+    fn every_third_cell_holds_anti_mines() {
+        let mut board = Board::new(6, 1, Topology::Square).with_max_mines(2).with_anti_mines(true);
+        place_with_anti_mines(&mut board, &[(1, 1), (2, 1), (3, 1), (3, 1), (4, 1), (5, 1), (6, 1)]);
+        let mines: Vec<i8> = (1..=6).map(|x| board.cell(x, 1).unwrap().mines).collect();
+        assert_eq!(mines, vec![1, 1, -2, 1, 1, -1]);
+    }
+}
@@ -0,0 +1,517 @@
+// Game rules without any Bevy types, so they can be driven from systems or used on their own.
+// Coordinates are 1-based (x = column, y = row), same as on `Tile`. Which cells are neighbours
+// is up to the board's `Topology` and `Neighbourhood`. A cell holds up to `max_mines` mines
+// (1 on classic boards), numbers count every mine around and flags are markers for 1 up to
+// `max_mines` of them.
+// Boards with `anti_mines` also have cells of anti-mines, stored as negative `mines`, which
+// take one off the numbers around them. They're marked with anti-flags (negative `flags`), and
+// opening one loses like a mine does.
+
+use std::collections::VecDeque;
+
+use crate::topology::{Neighbourhood, Topology};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reveal {
+    Nothing,
+    Safe,
+    Exploded,
+}
+
+// Mines per cell on classic boards, also what saves and replays without a count had.
+pub fn classic_mines() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub num: i8,
+    pub mines: i8,
+    pub covered: bool,
+    pub flags: i8,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell { num: 0, mines: 0, covered: true, flags: 0 }
+    }
+}
+
+impl Cell {
+    pub fn bomb(&self) -> bool {
+        self.mines != 0
+    }
+
+    pub fn flag(&self) -> bool {
+        self.flags != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Board {
+    width: u16,
+    height: u16,
+    topology: Topology,
+    neighbourhood: Neighbourhood,
+    max_mines: u8,
+    anti_mines: bool,
+    cells: Vec<Cell>,
+}
+
+impl Board {
+    pub fn new(width: u16, height: u16, topology: Topology) -> Self {
+        Board {
+            width,
+            height,
+            topology,
+            neighbourhood: Neighbourhood::King,
+            max_mines: classic_mines(),
+            anti_mines: false,
+            cells: vec![Cell::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        self.neighbourhood = self.topology.uses(neighbourhood);
+        self
+    }
+
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    pub fn with_max_mines(mut self, max_mines: u8) -> Self {
+        self.max_mines = max_mines.max(1);
+        self
+    }
+
+    pub fn max_mines(&self) -> u8 {
+        self.max_mines
+    }
+
+    pub fn with_anti_mines(mut self, anti_mines: bool) -> Self {
+        self.anti_mines = anti_mines;
+        self
+    }
+
+    pub fn anti_mines(&self) -> bool {
+        self.anti_mines
+    }
+
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            return None;
+        }
+        Some((y as usize - 1) * self.width as usize + (x as usize - 1))
+    }
+
+    pub fn cell(&self, x: u16, y: u16) -> Option<&Cell> {
+        self.index(x, y).map(|i| &self.cells[i])
+    }
+
+    pub fn set_cell(&mut self, x: u16, y: u16, cell: Cell) {
+        if let Some(old) = self.cell_mut(x, y) {
+            *old = cell;
+        }
+    }
+
+    pub fn bombs(&self) -> usize {
+        self.cells.iter().map(|cell| cell.mines.unsigned_abs() as usize).sum()
+    }
+
+    fn cell_mut(&mut self, x: u16, y: u16) -> Option<&mut Cell> {
+        self.index(x, y).map(|i| &mut self.cells[i])
+    }
+
+    pub fn neighbours(&self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        self.topology.neighbours(self.neighbourhood, x, y, self.width, self.height)
+    }
+
+    /// Puts a bomb on the given cells, once more for every time a cell is listed again, and
+    /// recomputes every `num`.
+    pub fn place_bombs(&mut self, positions: &[(u16, u16)]) {
+        for &(x, y) in positions {
+            if let Some(cell) = self.cell_mut(x, y) {
+                cell.mines += 1;
+            }
+        }
+        self.count_numbers();
+    }
+
+    /// Same as `place_bombs` with anti-mines.
+    pub fn place_anti_mines(&mut self, positions: &[(u16, u16)]) {
+        for &(x, y) in positions {
+            if let Some(cell) = self.cell_mut(x, y) {
+                cell.mines -= 1;
+            }
+        }
+        self.count_numbers();
+    }
+
+    fn count_numbers(&mut self) {
+        for y in 1..=self.height {
+            for x in 1..=self.width {
+                let num = self
+                    .neighbours(x, y)
+                    .into_iter()
+                    .map(|(nx, ny)| self.cell(nx, ny).map_or(0, |c| c.mines))
+                    .sum();
+                if let Some(cell) = self.cell_mut(x, y) {
+                    cell.num = if cell.bomb() { 0 } else { num };
+                }
+            }
+        }
+    }
+
+    // No mine or anti-mine around, with anti-mines a zero doesn't tell.
+    fn clear_around(&self, x: u16, y: u16) -> bool {
+        self.neighbours(x, y).into_iter().all(|(nx, ny)| !self.cell(nx, ny).unwrap().bomb())
+    }
+
+    /// Uncovers a cell. Opening one with nothing around flood fills the whole empty region
+    /// (breadth first), together with the numbers on its border.
+    pub fn reveal(&mut self, x: u16, y: u16) -> Reveal {
+        let cell = match self.cell_mut(x, y) {
+            Some(cell) if cell.covered && !cell.flag() => cell,
+            _ => return Reveal::Nothing,
+        };
+        cell.covered = false;
+        if cell.bomb() {
+            return Reveal::Exploded;
+        }
+        if !self.clear_around(x, y) {
+            return Reveal::Safe;
+        }
+
+        let mut queue = VecDeque::new();
+        queue.push_back((x, y));
+        while let Some((cx, cy)) = queue.pop_front() {
+            for (nx, ny) in self.neighbours(cx, cy) {
+                let neighbour = self.cell_mut(nx, ny).unwrap();
+                if !neighbour.covered || neighbour.flag() || neighbour.bomb() {
+                    continue;
+                }
+                neighbour.covered = false;
+                if self.clear_around(nx, ny) {
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        Reveal::Safe
+    }
+
+    /// Steps the cell through 1 up to `max_mines` flags, then as many anti-flags on boards with
+    /// anti-mines, then back to none. Returns whether the cell is flagged now, `None` if it
+    /// can't be flagged.
+    pub fn toggle_flag(&mut self, x: u16, y: u16) -> Option<bool> {
+        let (max_mines, anti_mines) = (self.max_mines as i8, self.anti_mines);
+        match self.cell_mut(x, y) {
+            Some(cell) if cell.covered => {
+                cell.flags = match cell.flags {
+                    flags if flags >= 0 && flags < max_mines => flags + 1,
+                    flags if flags > 0 && anti_mines => -1,
+                    flags if flags < 0 && flags > -max_mines => flags - 1,
+                    _ => 0,
+                };
+                Some(cell.flag())
+            }
+            _ => None,
+        }
+    }
+
+    // Anti-flags count as minus one.
+    pub fn flag_count(&self, x: u16, y: u16) -> i8 {
+        self.neighbours(x, y)
+            .into_iter()
+            .map(|(nx, ny)| self.cell(nx, ny).map_or(0, |c| c.flags))
+            .sum()
+    }
+
+    pub fn flags(&self) -> usize {
+        self.cells.iter().map(|cell| cell.flags.unsigned_abs() as usize).sum()
+    }
+
+    /// Reveals every unflagged neighbour of an uncovered number once enough flags are placed
+    /// around it. A wrongly placed flag means one of them is a bomb.
+    pub fn chord(&mut self, x: u16, y: u16) -> Reveal {
+        let num = match self.cell(x, y) {
+            Some(cell) if !cell.covered && !cell.bomb() => cell.num,
+            _ => return Reveal::Nothing,
+        };
+        // a zero only chords once flags and anti-flags around it cancel out
+        let flagged = self.neighbours(x, y).into_iter().any(|(nx, ny)| self.cell(nx, ny).unwrap().flag());
+        if !flagged || self.flag_count(x, y) != num {
+            return Reveal::Nothing;
+        }
+        let mut result = Reveal::Nothing;
+        for (nx, ny) in self.neighbours(x, y) {
+            match self.reveal(nx, ny) {
+                Reveal::Exploded => result = Reveal::Exploded,
+                Reveal::Safe if result == Reveal::Nothing => result = Reveal::Safe,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    /// Flags every bomb that isn't flagged yet, used once the board is cleared.
+    pub fn flag_bombs(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.flags = cell.mines;
+        }
+    }
+
+    /// Minimum number of clicks needed to clear the board (3BV): one per opening plus one per
+    /// number that doesn't border any opening.
+    pub fn three_bv(&self) -> u32 {
+        let mut marked = vec![false; self.cells.len()];
+        let mut result = 0;
+        for y in 1..=self.height {
+            for x in 1..=self.width {
+                let i = self.index(x, y).unwrap();
+                let cell = self.cells[i];
+                if marked[i] || cell.bomb() || !self.clear_around(x, y) {
+                    continue;
+                }
+                result += 1;
+                marked[i] = true;
+                let mut queue = VecDeque::new();
+                queue.push_back((x, y));
+                while let Some((cx, cy)) = queue.pop_front() {
+                    for (nx, ny) in self.neighbours(cx, cy) {
+                        let n = self.index(nx, ny).unwrap();
+                        if marked[n] || self.cells[n].bomb() {
+                            continue;
+                        }
+                        marked[n] = true;
+                        if self.clear_around(nx, ny) {
+                            queue.push_back((nx, ny));
+                        }
+                    }
+                }
+            }
+        }
+        result + self
+            .cells
+            .iter()
+            .zip(marked)
+            .filter(|(cell, marked)| !cell.bomb() && !marked)
+            .count() as u32
+    }
+
+    pub fn is_won(&self) -> bool {
+        self.cells.iter().all(|cell| cell.bomb() == cell.covered)
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.cells.iter().any(|cell| cell.bomb() && !cell.covered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(width: u16, height: u16, mines: &[(u16, u16)]) -> Board {
+        let mut board = Board::new(width, height, Topology::Square);
+        board.place_bombs(mines);
+        board
+    }
+
+    fn num(board: &Board, x: u16, y: u16) -> i8 {
+        board.cell(x, y).unwrap().num
+    }
+
+    #[test]
+    fn numbers_count_the_bombs_around() {
+        let board = board(3, 3, &[(1, 1), (3, 1)]);
+        assert_eq!(num(&board, 2, 1), 2);
+        assert_eq!(num(&board, 2, 2), 2);
+        assert_eq!(num(&board, 1, 2), 1);
+        assert_eq!(num(&board, 2, 3), 0);
+        assert_eq!(board.bombs(), 2);
+    }
+
+    #[test]
+    fn reveal_opens_a_number_on_its_own() {
+        let mut board = board(3, 3, &[(1, 1)]);
+        assert_eq!(board.reveal(2, 2), Reveal::Safe);
+        assert!(!board.cell(2, 2).unwrap().covered);
+        assert!(board.cell(3, 3).unwrap().covered);
+        assert_eq!(board.reveal(2, 2), Reveal::Nothing);
+        assert!(!board.is_lost());
+    }
+
+    #[test]
+    fn reveal_on_a_bomb_loses() {
+        let mut board = board(3, 3, &[(1, 1)]);
+        assert_eq!(board.reveal(1, 1), Reveal::Exploded);
+        assert!(board.is_lost());
+        assert!(!board.is_won());
+    }
+
+    #[test]
+    fn flags_toggle_and_block_reveals() {
+        let mut board = board(3, 3, &[(1, 1)]);
+        assert_eq!(board.toggle_flag(1, 1), Some(true));
+        assert_eq!(board.reveal(1, 1), Reveal::Nothing);
+        assert_eq!(board.flags(), 1);
+        assert_eq!(board.toggle_flag(1, 1), Some(false));
+        assert_eq!(board.flags(), 0);
+
+        board.reveal(2, 2);
+        assert_eq!(board.toggle_flag(2, 2), None);
+        assert_eq!(board.toggle_flag(0, 0), None);
+    }
+
+    #[test]
+    fn won_once_every_safe_cell_is_open() {
+        let mut board = board(2, 1, &[(1, 1)]);
+        assert!(!board.is_won());
+        board.reveal(2, 1);
+        assert!(board.is_won());
+        assert!(!board.is_lost());
+    }
+
+    #[test]
+    fn empty_cells_flood_fill_up_to_the_numbers() {
+        let wall = [(1, 3), (2, 3), (3, 3), (4, 3), (5, 3)];
+        let mut board = board(5, 5, &wall);
+        board.toggle_flag(5, 1);
+        assert_eq!(board.reveal(1, 1), Reveal::Safe);
+        for x in 1..=4 {
+            assert!(!board.cell(x, 1).unwrap().covered);
+            assert!(!board.cell(x, 2).unwrap().covered);
+            assert!(board.cell(x, 4).unwrap().covered);
+        }
+        // flags stop the fill
+        assert!(board.cell(5, 1).unwrap().covered);
+        assert!(!board.cell(5, 2).unwrap().covered);
+    }
+
+    #[test]
+    fn flood_fill_clears_a_board_in_one_reveal() {
+        let mut board = board(5, 5, &[(5, 5)]);
+        board.reveal(1, 1);
+        assert!(board.is_won());
+        assert!(board.cell(5, 5).unwrap().covered);
+    }
+
+    #[test]
+    fn chord_needs_matching_flags() {
+        let mut board = board(3, 3, &[(1, 1)]);
+        board.reveal(2, 2);
+        assert_eq!(board.chord(2, 2), Reveal::Nothing);
+        board.toggle_flag(1, 1);
+        assert_eq!(board.chord(2, 2), Reveal::Safe);
+        assert!(board.is_won());
+        // covered cells don't chord
+        assert_eq!(board.chord(1, 1), Reveal::Nothing);
+    }
+
+    #[test]
+    fn chord_with_a_wrong_flag_explodes() {
+        let mut board = board(3, 3, &[(1, 1)]);
+        board.reveal(2, 2);
+        board.toggle_flag(3, 3);
+        assert_eq!(board.chord(2, 2), Reveal::Exploded);
+        assert!(board.is_lost());
+    }
+
+    #[test]
+    fn three_bv_counts_openings_and_lone_numbers() {
+        // the numbers all border the one opening
+        assert_eq!(board(3, 3, &[(1, 1)]).three_bv(), 1);
+        // an opening on each side of the mine
+        assert_eq!(board(5, 1, &[(3, 1)]).three_bv(), 2);
+        // no opening, the number takes a click of its own
+        assert_eq!(board(3, 1, &[(1, 1), (3, 1)]).three_bv(), 1);
+        assert_eq!(board(4, 1, &[(1, 1), (3, 1)]).three_bv(), 2);
+    }
+
+    #[test]
+    fn numbers_follow_the_neighbourhood() {
+        let mut board = Board::new(3, 3, Topology::Square).with_neighbourhood(Neighbourhood::Knight);
+        board.place_bombs(&[(1, 1)]);
+        assert_eq!(num(&board, 2, 3), 1);
+        assert_eq!(num(&board, 3, 2), 1);
+        assert_eq!(num(&board, 2, 2), 0);
+        assert_eq!(num(&board, 2, 1), 0);
+        // (2, 2) reaches no cell a knight's move away, so nothing else opens
+        board.reveal(2, 2);
+        assert!(board.cell(2, 1).unwrap().covered);
+        board.reveal(3, 3);
+        assert!(!board.cell(2, 1).unwrap().covered);
+    }
+
+    #[test]
+    fn cells_hold_several_mines_and_flags() {
+        let mut board = Board::new(3, 1, Topology::Square).with_max_mines(3);
+        board.place_bombs(&[(1, 1), (1, 1), (3, 1)]);
+        assert_eq!(num(&board, 2, 1), 3);
+        assert_eq!(board.bombs(), 3);
+        let flags: Vec<i8> = (0..4)
+            .map(|_| {
+                board.toggle_flag(1, 1);
+                board.cell(1, 1).unwrap().flags
+            })
+            .collect();
+        assert_eq!(flags, vec![1, 2, 3, 0]);
+
+        board.reveal(2, 1);
+        board.toggle_flag(1, 1);
+        board.toggle_flag(3, 1);
+        assert_eq!(board.chord(2, 1), Reveal::Nothing);
+        board.toggle_flag(1, 1);
+        assert_eq!(board.flag_count(2, 1), 3);
+        assert_eq!(board.flags(), 3);
+    }
+
+    #[test]
+    fn anti_mines_take_from_the_numbers() {
+        let mut board = Board::new(3, 1, Topology::Square).with_anti_mines(true);
+        board.place_anti_mines(&[(1, 1)]);
+        assert_eq!(num(&board, 2, 1), -1);
+        board.place_bombs(&[(3, 1)]);
+        assert_eq!(num(&board, 2, 1), 0);
+        assert_eq!(board.bombs(), 2);
+        // a zero with mines around doesn't open them
+        assert_eq!(board.reveal(2, 1), Reveal::Safe);
+        assert!(board.cell(1, 1).unwrap().covered && board.cell(3, 1).unwrap().covered);
+        assert!(board.is_won());
+    }
+
+    #[test]
+    fn anti_flags_follow_the_flags() {
+        let mut board = Board::new(3, 1, Topology::Square).with_max_mines(2).with_anti_mines(true);
+        board.place_anti_mines(&[(1, 1)]);
+        let flags: Vec<i8> = (0..5)
+            .map(|_| {
+                board.toggle_flag(1, 1);
+                board.cell(1, 1).unwrap().flags
+            })
+            .collect();
+        assert_eq!(flags, vec![1, 2, -1, -2, 0]);
+
+        board.reveal(2, 1);
+        board.toggle_flag(1, 1);
+        assert_eq!(board.chord(2, 1), Reveal::Nothing);
+        board.toggle_flag(1, 1);
+        board.toggle_flag(1, 1);
+        assert_eq!(board.flag_count(2, 1), -1);
+        assert_eq!(board.chord(2, 1), Reveal::Safe);
+        assert!(board.is_won());
+    }
+}
@@ -0,0 +1,197 @@
+// Playing without a mouse: a cursor moved with the arrow keys, WASD, HJKL or a gamepad d-pad.
+//
+//     Space / Enter, gamepad South   reveal (chord on an uncovered number)
+//     F, gamepad East                flag
+//     X, gamepad West                chord
+//     R, gamepad Start               new game
+//
+// The cursor shows up on the first key or button press and hides again when the mouse moves.
+// A first move only brings it up where it was, any other key acts on that tile straight away.
+
+use bevy::prelude::*;
+
+use crate::{
+    hud::GameTimer, play, replay::{ActionKind, PlayerAction}, start_game, BoardInput, CurrentBoard, GameState,
+    MapInfo, Safe, TileIndex, TILE_SIZE,
+};
+
+const CURSOR_COLOR: Color = Color::rgb(1.0, 0.85, 0.0);
+const CURSOR_WIDTH: f32 = 3.0;
+
+const LEFT: [KeyCode; 3] = [KeyCode::Left, KeyCode::A, KeyCode::H];
+const RIGHT: [KeyCode; 3] = [KeyCode::Right, KeyCode::D, KeyCode::L];
+const UP: [KeyCode; 3] = [KeyCode::Up, KeyCode::W, KeyCode::K];
+const DOWN: [KeyCode; 3] = [KeyCode::Down, KeyCode::S, KeyCode::J];
+
+#[derive(Resource)]
+pub struct BoardCursor {
+    pub x: u16,
+    pub y: u16,
+    pub visible: bool,
+}
+
+impl Default for BoardCursor {
+    fn default() -> Self {
+        BoardCursor { x: 1, y: 1, visible: false }
+    }
+}
+
+#[derive(Component)]
+struct CursorFrame;
+
+// What the keyboard and gamepads asked for this frame.
+#[derive(Default)]
+struct Pressed {
+    moved: (i32, i32),
+    reveal: bool,
+    flag: bool,
+    chord: bool,
+    new_game: bool,
+}
+
+impl Pressed {
+    fn any(&self) -> bool {
+        self.moved != (0, 0) || self.reveal || self.flag || self.chord || self.new_game
+    }
+}
+
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BoardCursor>()
+            .add_system(spawn_frame.in_schedule(OnExit(GameState::AssetLoading)))
+            .add_system(cursor_input.in_set(BoardInput))
+            .add_systems((hide_on_mouse, draw_cursor).chain().after(cursor_input));
+    }
+}
+
+fn spawn_frame(mut commands: Commands) {
+    let side = TILE_SIZE - CURSOR_WIDTH;
+    let horizontal = Vec2::new(TILE_SIZE, CURSOR_WIDTH);
+    let vertical = Vec2::new(CURSOR_WIDTH, TILE_SIZE);
+    commands
+        .spawn((SpatialBundle { visibility: Visibility::Hidden, ..default() }, CursorFrame, Name::new("Cursor")))
+        .with_children(|parent| {
+            for (position, size) in [
+                (Vec2::new(0.0, side / 2.0), horizontal),
+                (Vec2::new(0.0, -side / 2.0), horizontal),
+                (Vec2::new(-side / 2.0, 0.0), vertical),
+                (Vec2::new(side / 2.0, 0.0), vertical),
+            ] {
+                parent.spawn(SpriteBundle {
+                    sprite: Sprite { color: CURSOR_COLOR, custom_size: Some(size), ..default() },
+                    transform: Transform::from_translation(position.extend(0.0)),
+                    ..default()
+                });
+            }
+        });
+}
+
+fn read_input(keys: &Input<KeyCode>, gamepads: &Gamepads, buttons: &Input<GamepadButton>) -> Pressed {
+    let pad = |kind: GamepadButtonType| gamepads.iter().any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, kind)));
+    let mut pressed = Pressed::default();
+    if keys.any_just_pressed(LEFT) || pad(GamepadButtonType::DPadLeft) {
+        pressed.moved.0 -= 1;
+    }
+    if keys.any_just_pressed(RIGHT) || pad(GamepadButtonType::DPadRight) {
+        pressed.moved.0 += 1;
+    }
+    if keys.any_just_pressed(UP) || pad(GamepadButtonType::DPadUp) {
+        pressed.moved.1 -= 1;
+    }
+    if keys.any_just_pressed(DOWN) || pad(GamepadButtonType::DPadDown) {
+        pressed.moved.1 += 1;
+    }
+    pressed.reveal = keys.any_just_pressed([KeyCode::Space, KeyCode::Return]) || pad(GamepadButtonType::South);
+    pressed.flag = keys.just_pressed(KeyCode::F) || pad(GamepadButtonType::East);
+    pressed.chord = keys.just_pressed(KeyCode::X) || pad(GamepadButtonType::West);
+    pressed.new_game = keys.just_pressed(KeyCode::R) || pad(GamepadButtonType::Start);
+    pressed
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cursor_input(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    state: Res<State<GameState>>,
+    map_info: Res<MapInfo>,
+    mut cursor: ResMut<BoardCursor>,
+    mut board: ResMut<CurrentBoard>,
+    mut safe: ResMut<Safe>,
+    mut timer: ResMut<GameTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    if state.0 == GameState::AssetLoading {
+        return;
+    }
+    let pressed = read_input(&keys, &gamepads, &buttons);
+    if !pressed.any() {
+        return;
+    }
+
+    if !cursor.visible {
+        cursor.visible = true;
+        if pressed.moved != (0, 0) {
+            return;
+        }
+    }
+
+    let (rows, cols) = map_info.board_size;
+    let (x, y) = (cursor.x as i32 + pressed.moved.0, cursor.y as i32 + pressed.moved.1);
+    // off one edge of a wrapping board and back in at the other
+    if map_info.topology.wraps() {
+        cursor.x = ((x - 1).rem_euclid(cols as i32) + 1) as u16;
+        cursor.y = ((y - 1).rem_euclid(rows as i32) + 1) as u16;
+    } else {
+        cursor.x = x.clamp(1, cols as i32) as u16;
+        cursor.y = y.clamp(1, rows as i32) as u16;
+    }
+    let (x, y) = (cursor.x, cursor.y);
+
+    if pressed.new_game {
+        next_state.set(GameState::SafeClick);
+        return;
+    }
+    match state.0 {
+        GameState::SafeClick if pressed.reveal => {
+            start_game((x, y), &mut safe, &mut timer, &mut next_state, &mut actions);
+        }
+        GameState::InGame => {
+            let uncovered = board.cell(x, y).is_some_and(|cell| !cell.covered);
+            if pressed.chord || (pressed.reveal && uncovered) {
+                play(&mut board, ActionKind::Chord, x, y, &mut actions);
+            } else if pressed.reveal {
+                play(&mut board, ActionKind::Reveal, x, y, &mut actions);
+            } else if pressed.flag {
+                play(&mut board, ActionKind::Flag, x, y, &mut actions);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn hide_on_mouse(mut moved: EventReader<CursorMoved>, mut cursor: ResMut<BoardCursor>) {
+    if moved.iter().count() > 0 && cursor.visible {
+        cursor.visible = false;
+    }
+}
+
+fn draw_cursor(
+    cursor: Res<BoardCursor>,
+    index: Res<TileIndex>,
+    mut frame: Query<(&mut Transform, &mut Visibility), With<CursorFrame>>,
+) {
+    let Ok((mut transform, mut visibility)) = frame.get_single_mut() else { return };
+    let shown = if cursor.visible && index.get(cursor.x, cursor.y).is_some() {
+        transform.translation = index.center(cursor.x, cursor.y).extend(0.9);
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+}
@@ -0,0 +1,320 @@
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+
+use crate::{fit_window, Fonts, GameState, MapInfo};
+
+const MIN_SIZE: u16 = 9;
+pub const MAX_SIZE: u16 = 99;
+
+pub const PANEL_COLOR: Color = Color::rgb(0.35, 0.39, 0.47);
+const FIELD_COLOR: Color = Color::rgb(0.5, 0.55, 0.63);
+const SELECTED_COLOR: Color = Color::rgb(0.85, 0.75, 0.35);
+
+#[derive(Resource, Default)]
+pub struct CustomGame {
+    pub open: bool,
+    fields: [String; 3],
+    selected: usize,
+    error: String,
+}
+
+// board input runs after this so a click on the dialog can be swallowed before it reaches the tiles
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CustomDialogSet;
+
+#[derive(Component)]
+struct CustomDialog;
+
+// put on the menu entry that opens it
+#[derive(Component)]
+pub struct CustomOpenButton;
+
+#[derive(Component)]
+struct Field(usize);
+
+#[derive(Component)]
+struct ErrorText;
+
+#[derive(Component)]
+enum DialogButton {
+    Start,
+    Cancel,
+}
+
+pub struct CustomGamePlugin;
+
+impl Plugin for CustomGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CustomGame>()
+            .add_systems(
+                (
+                    open_dialog,
+                    dialog_input,
+                    dialog_buttons,
+                    apply_system_buffers,
+                    update_dialog,
+                )
+                    .chain()
+                    .distributive_run_if(not_loading)
+                    .in_set(CustomDialogSet),
+            );
+    }
+}
+
+pub fn dialog_closed(custom: Res<CustomGame>) -> bool {
+    !custom.open
+}
+
+fn not_loading(state: Res<State<GameState>>) -> bool {
+    state.0 != GameState::AssetLoading
+}
+
+fn open_dialog(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    open_button: Query<&Interaction, (Changed<Interaction>, With<CustomOpenButton>)>,
+    mut custom: ResMut<CustomGame>,
+    map_info: Res<MapInfo>,
+    fonts: Res<Fonts>,
+) {
+    let clicked = open_button.iter().any(|interaction| *interaction == Interaction::Clicked);
+    if custom.open || !(clicked || keys.just_pressed(KeyCode::C)) {
+        return;
+    }
+
+    custom.open = true;
+    custom.fields = [
+        map_info.board_size.1.to_string(),
+        map_info.board_size.0.to_string(),
+        map_info.bomb_count.to_string(),
+    ];
+    custom.selected = 0;
+    custom.error.clear();
+
+    let text_style = TextStyle { font: fonts.main.clone(), font_size: 20.0, color: Color::WHITE };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                ..default()
+            },
+            CustomDialog,
+            Name::new("Custom game"),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Stretch,
+                        padding: UiRect::all(Val::Px(10.0)),
+                        gap: Size::all(Val::Px(6.0)),
+                        ..default()
+                    },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|panel| {
+                    for i in 0..3 {
+                        panel
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::all(Val::Px(4.0)),
+                                        ..default()
+                                    },
+                                    background_color: FIELD_COLOR.into(),
+                                    ..default()
+                                },
+                                Field(i),
+                            ))
+                            .with_children(|field| {
+                                field.spawn((TextBundle::from_section("", text_style.clone()), Field(i)));
+                            });
+                    }
+                    panel.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle { font_size: 14.0, color: SELECTED_COLOR, ..text_style.clone() },
+                        ),
+                        ErrorText,
+                    ));
+                    panel
+                        .spawn(NodeBundle {
+                            style: Style {
+                                justify_content: JustifyContent::SpaceBetween,
+                                gap: Size::all(Val::Px(6.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            for (button, label) in [(DialogButton::Start, "Start"), (DialogButton::Cancel, "Cancel")] {
+                                row.spawn((
+                                    ButtonBundle {
+                                        style: Style {
+                                            padding: UiRect::all(Val::Px(4.0)),
+                                            ..default()
+                                        },
+                                        background_color: FIELD_COLOR.into(),
+                                        ..default()
+                                    },
+                                    button,
+                                ))
+                                .with_children(|button| {
+                                    button.spawn(TextBundle::from_section(label, text_style.clone()));
+                                });
+                            }
+                        });
+                });
+        });
+}
+
+fn close_dialog(commands: &mut Commands, custom: &mut CustomGame) {
+    custom.open = false;
+    commands.despawn_all_recursive::<With<CustomDialog>>();
+}
+
+// Checks the typed numbers and starts the new board, or leaves an error in the dialog.
+fn start_custom(
+    commands: &mut Commands,
+    custom: &mut CustomGame,
+    map_info: &mut MapInfo,
+    window: &mut Window,
+    camera: &mut Transform,
+    next_state: &mut NextState<GameState>,
+) {
+    let values: Vec<Option<u16>> = custom.fields.iter().map(|field| field.parse().ok()).collect();
+    let (width, height, mines) = match values[..] {
+        [Some(width), Some(height), Some(mines)] => (width, height, mines),
+        _ => {
+            custom.error = "Fill in all three numbers".to_string();
+            return;
+        }
+    };
+    if !(MIN_SIZE..=MAX_SIZE).contains(&width) || !(MIN_SIZE..=MAX_SIZE).contains(&height) {
+        custom.error = format!("Width and height must be {}-{}", MIN_SIZE, MAX_SIZE);
+        return;
+    }
+    let most_mines = (width as u32 * height as u32 - map_info.safe_zone()) * map_info.max_mines as u32;
+    if mines == 0 || mines as u32 > most_mines {
+        custom.error = format!("Mines must be 1-{}", most_mines);
+        return;
+    }
+
+    map_info.board_size = (height, width);
+    map_info.bomb_count = mines;
+    fit_window(window, camera, map_info);
+    next_state.set(GameState::SafeClick);
+    close_dialog(commands, custom);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn dialog_input(
+    mut commands: Commands,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut custom: ResMut<CustomGame>,
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !custom.open {
+        characters.clear();
+        return;
+    }
+    let selected = custom.selected;
+    for event in characters.iter() {
+        if event.char.is_ascii_digit() && custom.fields[selected].len() < 4 {
+            custom.fields[selected].push(event.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        custom.fields[selected].pop();
+    }
+    if keys.just_pressed(KeyCode::Tab) || keys.just_pressed(KeyCode::Down) {
+        custom.selected = (selected + 1) % 3;
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        custom.selected = (selected + 2) % 3;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        close_dialog(&mut commands, &mut custom);
+    } else if keys.just_pressed(KeyCode::Return) {
+        // the board cursor reveals on Enter too
+        keys.clear_just_pressed(KeyCode::Return);
+        start_custom(
+            &mut commands,
+            &mut custom,
+            &mut map_info,
+            &mut window.single_mut(),
+            &mut camera.single_mut(),
+            &mut next_state,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn dialog_buttons(
+    mut commands: Commands,
+    mut mouse: ResMut<Input<MouseButton>>,
+    fields: Query<(&Interaction, &Field), Changed<Interaction>>,
+    buttons: Query<(&Interaction, &DialogButton), Changed<Interaction>>,
+    mut custom: ResMut<CustomGame>,
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, field) in fields.iter() {
+        if *interaction == Interaction::Clicked {
+            custom.selected = field.0;
+        }
+    }
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        mouse.clear_just_pressed(MouseButton::Left);
+        match button {
+            DialogButton::Start => start_custom(
+                &mut commands,
+                &mut custom,
+                &mut map_info,
+                &mut window.single_mut(),
+                &mut camera.single_mut(),
+                &mut next_state,
+            ),
+            DialogButton::Cancel => close_dialog(&mut commands, &mut custom),
+        }
+    }
+}
+
+fn update_dialog(
+    custom: Res<CustomGame>,
+    mut field_texts: Query<(&mut Text, &Field)>,
+    mut field_buttons: Query<(&mut BackgroundColor, &Field), With<Interaction>>,
+    mut error: Query<&mut Text, (With<ErrorText>, Without<Field>)>,
+) {
+    if !custom.is_changed() || !custom.open {
+        return;
+    }
+    const LABELS: [&str; 3] = ["Width", "Height", "Mines"];
+    for (mut text, field) in field_texts.iter_mut() {
+        text.sections[0].value = format!("{}: {}", LABELS[field.0], custom.fields[field.0]);
+    }
+    for (mut color, field) in field_buttons.iter_mut() {
+        *color = if field.0 == custom.selected { SELECTED_COLOR } else { FIELD_COLOR }.into();
+    }
+    for mut text in error.iter_mut() {
+        text.sections[0].value = custom.error.clone();
+    }
+}
@@ -0,0 +1,322 @@
+// Fixed mine layouts read from and written to text.
+//
+// Plain grid: one line per row, `*` is a mine and `.` a safe tile.
+//
+//     ..*.
+//     *...
+//
+// RAW board: the same grid after a `Width`, `Height` and `Mines` header (`Key=value` or
+// `Key: value`), as exported by Minesweeper analysis tools. Safe tiles may also be written as
+// their number, which is what the export does. Hex and torus boards are exported with a
+// `Topology=Hex` or `Topology=Torus` line added to the header, a board without one is square.
+// Other neighbourhoods get a `Neighbourhood=Knight` (Manhattan, Cross) line the same way, and
+// numbers above 8 are exported as `.` since they don't fit in one character.
+//
+//     Width=4
+//     Height=2
+//     Mines=2
+//     12*1
+//     *211
+//
+// Boards of up to `MAX_SIZE` (the custom game limit) each way with at least one mine can be
+// imported, smaller ones than a custom game allows are fine.
+//
+// I imports `board.txt` from the data directory (localStorage in the browser), E exports the
+// current layout as `export.txt` and `export.raw.txt`. Natively a file can also be dropped on
+// the window or passed with `--board <path>`.
+
+use bevy::prelude::*;
+
+use crate::{
+    board::{classic_mines, Board}, custom::{dialog_closed, MAX_SIZE}, hud::GameTimer, replay::not_playing,
+    save::{load_autosave, start_restore, PendingRestore, SavedGame}, storage,
+    topology::{Neighbourhood, Topology}, CurrentBoard, GameState, MapInfo,
+};
+
+const IMPORT_FILE: &str = "board.txt";
+const EXPORT_GRID: &str = "export.txt";
+const EXPORT_RAW: &str = "export.raw.txt";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub width: u16,
+    pub height: u16,
+    pub topology: Topology,
+    pub neighbourhood: Neighbourhood,
+    pub mines: Vec<(u16, u16)>,
+}
+
+impl Layout {
+    pub fn parse(text: &str) -> Result<Layout, String> {
+        let mut header = (None, None, None);
+        let mut topology = Topology::Square;
+        let mut neighbourhood = Neighbourhood::King;
+        let mut rows = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some((key, value)) = line.split_once(['=', ':']) {
+                if key.trim().eq_ignore_ascii_case("topology") {
+                    topology = Topology::from_name(value.trim()).ok_or(format!("Unknown topology \"{}\"", value.trim()))?;
+                    continue;
+                }
+                if key.trim().eq_ignore_ascii_case("neighbourhood") {
+                    neighbourhood = Neighbourhood::from_name(value.trim())
+                        .ok_or(format!("Unknown neighbourhood \"{}\"", value.trim()))?;
+                    continue;
+                }
+                let value: u16 = value.trim().parse().map_err(|_| format!("Bad number in \"{}\"", line))?;
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "width" => header.0 = Some(value),
+                    "height" => header.1 = Some(value),
+                    "mines" => header.2 = Some(value),
+                    _ => return Err(format!("Unknown header \"{}\"", key.trim())),
+                }
+            } else {
+                rows.push(line);
+            }
+        }
+
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 || height == 0 {
+            return Err("No board rows".to_string());
+        }
+        if width > MAX_SIZE as usize || height > MAX_SIZE as usize {
+            return Err(format!("Board is {}x{}, at most {}x{} can be imported", width, height, MAX_SIZE, MAX_SIZE));
+        }
+        let (width, height) = (width as u16, height as u16);
+        let mut mines = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width as usize {
+                return Err(format!("Row {} has a different width", y + 1));
+            }
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '*' => mines.push((x as u16 + 1, y as u16 + 1)),
+                    '.' | 'o' | '0'..='8' => {}
+                    _ => return Err(format!("Unexpected '{}' in row {}", c, y + 1)),
+                }
+            }
+        }
+
+        if mines.is_empty() {
+            return Err("Board has no mines".to_string());
+        }
+        if header.0.is_some_and(|w| w != width) || header.1.is_some_and(|h| h != height) {
+            return Err("Header size doesn't match the grid".to_string());
+        }
+        if header.2.is_some_and(|m| m as usize != mines.len()) {
+            return Err("Header mine count doesn't match the grid".to_string());
+        }
+        Ok(Layout { width, height, topology, neighbourhood, mines })
+    }
+
+    pub fn from_board(board: &Board) -> Layout {
+        let mut mines = Vec::new();
+        for y in 1..=board.height() {
+            for x in 1..=board.width() {
+                if board.cell(x, y).unwrap().bomb() {
+                    mines.push((x, y));
+                }
+            }
+        }
+        Layout {
+            width: board.width(),
+            height: board.height(),
+            topology: board.topology(),
+            neighbourhood: board.neighbourhood(),
+            mines,
+        }
+    }
+
+    pub fn board(&self) -> Board {
+        let mut board = Board::new(self.width, self.height, self.topology).with_neighbourhood(self.neighbourhood);
+        board.place_bombs(&self.mines);
+        board
+    }
+
+    pub fn to_grid(&self) -> String {
+        let board = self.board();
+        let mut text = String::new();
+        for y in 1..=self.height {
+            for x in 1..=self.width {
+                text.push(if board.cell(x, y).unwrap().bomb() { '*' } else { '.' });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn to_raw(&self) -> String {
+        let board = self.board();
+        let mut text = format!("Width={}\nHeight={}\nMines={}\n", self.width, self.height, self.mines.len());
+        if self.topology != Topology::Square {
+            text += &format!("Topology={}\n", self.topology.name());
+        }
+        if board.neighbourhood() != Neighbourhood::King {
+            text += &format!("Neighbourhood={}\n", board.neighbourhood().name());
+        }
+        for y in 1..=self.height {
+            for x in 1..=self.width {
+                let cell = board.cell(x, y).unwrap();
+                text.push(match cell.num {
+                    _ if cell.bomb() => '*',
+                    0..=8 => char::from(b'0' + cell.num as u8),
+                    _ => '.',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+pub struct FormatsPlugin;
+
+impl Plugin for FormatsPlugin {
+    fn build(&self, app: &mut App) {
+        // a board given on the command line wins over the autosave
+        app.add_system(import_from_args.after(load_autosave).in_schedule(OnExit(GameState::AssetLoading)))
+            .add_system(import_export.run_if(dialog_closed).run_if(not_playing));
+    }
+}
+
+// Starts the layout as an already generated game, `set_bombs` leaves boards with bombs alone.
+// The timer stays stopped until the first reveal since nothing is opened yet.
+fn start_layout(
+    layout: Layout,
+    map_info: &mut MapInfo,
+    window: &mut Window,
+    camera: &mut Transform,
+    pending: &mut PendingRestore,
+    next_state: &mut NextState<GameState>,
+) {
+    let info = MapInfo {
+        board_size: (layout.height, layout.width),
+        bomb_count: layout.mines.len() as u16,
+        topology: layout.topology,
+        neighbourhood: layout.neighbourhood,
+        max_mines: classic_mines(),
+        anti_mines: false,
+    };
+    let save = SavedGame::capture(&layout.board(), &info, 0, GameTimer::default().elapsed, GameState::InGame);
+    println!("Loaded {}x{} board with {} mines", layout.width, layout.height, layout.mines.len());
+    start_restore(save, map_info, window, camera, pending, next_state);
+}
+
+fn import_from_args(
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut pending: ResMut<PendingRestore>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(path) = args.iter().position(|arg| arg == "--board").and_then(|i| args.get(i + 1)) else { return };
+    match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| Layout::parse(&text)) {
+        Ok(layout) => {
+            start_layout(layout, &mut map_info, &mut window.single_mut(), &mut camera.single_mut(), &mut pending, &mut next_state)
+        }
+        Err(e) => println!("Couldn't load {}: {}", path, e),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn import_export(
+    keys: Res<Input<KeyCode>>,
+    mut dropped: EventReader<FileDragAndDrop>,
+    state: Res<State<GameState>>,
+    board: Res<CurrentBoard>,
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut pending: ResMut<PendingRestore>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if state.0 == GameState::AssetLoading {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::E) {
+        if board.bombs() == 0 {
+            println!("Nothing to export before the first click");
+        } else if board.max_mines() > 1 || board.anti_mines() {
+            println!("Only boards with one plain mine per cell can be exported");
+        } else {
+            let layout = Layout::from_board(&board);
+            for (name, text) in [(EXPORT_GRID, layout.to_grid()), (EXPORT_RAW, layout.to_raw())] {
+                match storage::save(name, &text) {
+                    Ok(()) => println!("Exported {}", name),
+                    Err(e) => println!("Couldn't export {}: {}", name, e),
+                }
+            }
+        }
+    }
+
+    let mut text = None;
+    if keys.just_pressed(KeyCode::I) {
+        text = Some(storage::load(IMPORT_FILE).ok_or(format!("No {} to import", IMPORT_FILE)));
+    }
+    for event in dropped.iter() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event {
+            text = Some(std::fs::read_to_string(path_buf).map_err(|e| e.to_string()));
+        }
+    }
+    match text.map(|text| text.and_then(|text| Layout::parse(&text))) {
+        Some(Ok(layout)) => {
+            start_layout(layout, &mut map_info, &mut window.single_mut(), &mut camera.single_mut(), &mut pending, &mut next_state)
+        }
+        Some(Err(e)) => println!("Couldn't import board: {}", e),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_plain_grid() {
+        let layout = Layout::parse("..*.\n*...\n").unwrap();
+        assert_eq!((layout.width, layout.height), (4, 2));
+        assert_eq!(layout.topology, Topology::Square);
+        assert_eq!(layout.mines, vec![(3, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn raw_export_reads_back_the_same() {
+        let layout = Layout::parse("Width: 4\nHeight: 2\nMines: 2\n12*1\n*211\n").unwrap();
+        assert_eq!(layout.to_raw(), "Width=4\nHeight=2\nMines=2\n12*1\n*211\n");
+        assert_eq!(Layout::parse(&layout.to_raw()).unwrap(), layout);
+        assert_eq!(Layout::parse(&layout.to_grid()).unwrap(), layout);
+    }
+
+    #[test]
+    fn variants_go_through_the_header() {
+        let layout = Layout::parse("Topology=Torus\nNeighbourhood=Knight\n*..\n...\n...\n").unwrap();
+        assert_eq!(layout.topology, Topology::Torus);
+        assert_eq!(layout.neighbourhood, Neighbourhood::Knight);
+        assert_eq!(Layout::parse(&layout.to_raw()).unwrap(), layout);
+    }
+
+    #[test]
+    fn rejects_broken_layouts() {
+        assert!(Layout::parse("").is_err());
+        assert!(Layout::parse("*..\n..\n").is_err());
+        assert!(Layout::parse("*.x\n").is_err());
+        assert!(Layout::parse("Width=3\n*..\n*..\n").is_ok());
+        assert!(Layout::parse("Width=4\n*..\n").is_err());
+        assert!(Layout::parse("Mines=2\n*..\n").is_err());
+        assert!(Layout::parse("Topology=Triangle\n*..\n").is_err());
+    }
+
+    #[test]
+    fn rejects_boards_without_mines_or_too_big() {
+        assert!(Layout::parse("...\n...\n").is_err());
+        let wide = format!("*{}\n", ".".repeat(MAX_SIZE as usize));
+        assert!(Layout::parse(&wide).is_err());
+        let tall = format!("*\n{}", ".\n".repeat(MAX_SIZE as usize));
+        assert!(Layout::parse(&tall).is_err());
+        let largest = format!("*{}\n", ".".repeat(MAX_SIZE as usize - 1));
+        assert!(Layout::parse(&largest).is_ok());
+    }
+}
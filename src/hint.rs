@@ -0,0 +1,93 @@
+// ? (the slash key) marks one tile the solver can prove safe (green) or a mine (red) and says
+// which rule proved it. The hint goes away with the next move.
+
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+
+use crate::{
+    custom::{dialog_closed, PANEL_COLOR}, replay::not_playing, solver::{self, Finding}, CurrentBoard, Fonts,
+    GameState, TileIndex, TILE_SIZE,
+};
+
+const SAFE_COLOR: Color = Color::rgba(0.2, 0.8, 0.2, 0.45);
+const MINE_COLOR: Color = Color::rgba(0.9, 0.15, 0.15, 0.45);
+
+#[derive(Component)]
+struct HintMarker;
+
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems((clear_hint, show_hint.run_if(dialog_closed).run_if(not_playing)).chain());
+    }
+}
+
+fn clear_hint(mut commands: Commands, board: Res<CurrentBoard>, state: Res<State<GameState>>) {
+    if board.is_changed() || state.is_changed() {
+        commands.despawn_all_recursive::<With<HintMarker>>();
+    }
+}
+
+fn show_hint(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    board: Res<CurrentBoard>,
+    index: Res<TileIndex>,
+    fonts: Option<Res<Fonts>>,
+) {
+    let Some(fonts) = fonts else { return };
+    if !keys.just_pressed(KeyCode::Slash) {
+        return;
+    }
+    commands.despawn_all_recursive::<With<HintMarker>>();
+
+    let text = if state.0 != GameState::InGame {
+        "Hints are given once the game has started".to_string()
+    } else if !solver::supported(&board) {
+        "Hints only work on boards with one plain mine per cell".to_string()
+    } else if let Some(deduction) = solver::hint(&board) {
+        let ((x, y), color) = match deduction.finding {
+            Finding::Safe(x, y) => ((x, y), SAFE_COLOR),
+            Finding::Mine(x, y) => ((x, y), MINE_COLOR),
+        };
+        if index.get(x, y).is_some() {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite { color, custom_size: Some(Vec2::splat(TILE_SIZE)), ..default() },
+                    transform: Transform::from_translation(index.center(x, y).extend(0.8)),
+                    ..default()
+                },
+                HintMarker,
+                Name::new("Hint"),
+            ));
+        }
+        deduction.explain(&board)
+    } else {
+        "Nothing can be proven, you have to guess".to_string()
+    };
+    println!("Hint: {}", text);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect { left: Val::Px(4.0), right: Val::Px(4.0), bottom: Val::Px(4.0), ..default() },
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: PANEL_COLOR.into(),
+                ..default()
+            },
+            HintMarker,
+            Name::new("Hint text"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle { font: fonts.main.clone(), font_size: 12.0, color: Color::WHITE },
+            ));
+        });
+}
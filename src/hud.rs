@@ -0,0 +1,217 @@
+use bevy::{prelude::*, utils::Duration};
+
+use crate::{replay::{ActionKind, PlayerAction}, CurrentBoard, GameState, MapInfo};
+
+const DIGITS: usize = 3;
+const DIGIT_WIDTH: f32 = 10.0;
+const DIGIT_HEIGHT: f32 = 20.0;
+const SEGMENT: f32 = 2.0;
+const SPACING: f32 = 3.0;
+
+const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const ON_COLOR: Color = Color::rgb(0.95, 0.15, 0.1);
+const OFF_COLOR: Color = Color::rgb(0.3, 0.08, 0.06);
+
+// segments a-g, bit 0 = a (top), clockwise, bit 6 = g (middle)
+const DIGIT_SEGMENTS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110,
+    0b1101101, 0b1111101, 0b0000111, 0b1111111, 0b1101111,
+];
+const MINUS_SEGMENTS: u8 = 0b1000000;
+
+// Time since the first click, stopped once the game ends.
+#[derive(Resource, Default, Debug)]
+pub struct GameTimer {
+    pub elapsed: Duration,
+    pub running: bool,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    Mines,
+    Timer,
+}
+
+#[derive(Component)]
+struct Digit(usize);
+
+#[derive(Component)]
+struct Segment(usize);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameTimer>()
+            .add_system(reset_timer.in_schedule(OnEnter(GameState::SafeClick)))
+            .add_system(stop_timer.in_schedule(OnEnter(GameState::GameOver)))
+            .add_system(stop_timer.in_schedule(OnEnter(GameState::Won)))
+            .add_system(start_timer.in_set(OnUpdate(GameState::InGame)))
+            .add_system(tick_timer)
+            .add_system(update_counters);
+    }
+}
+
+// Three seven-segment digits, spawned into the menu bar.
+pub fn spawn_counter(parent: &mut ChildBuilder, counter: Counter) {
+    let width = DIGITS as f32 * DIGIT_WIDTH + (DIGITS + 1) as f32 * SPACING;
+    let height = DIGIT_HEIGHT + 2.0 * SPACING;
+
+    parent
+        .spawn((
+            NodeBundle {
+                style: Style { size: Size::new(Val::Px(width), Val::Px(height)), ..default() },
+                background_color: BACKGROUND_COLOR.into(),
+                ..default()
+            },
+            counter,
+            Name::new(if counter == Counter::Mines { "Mine counter" } else { "Timer" }),
+        ))
+        .with_children(|parent| {
+            for i in 0..DIGITS {
+                let left = SPACING + i as f32 * (DIGIT_WIDTH + SPACING);
+                parent
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: UiRect { left: Val::Px(left), top: Val::Px(SPACING), ..default() },
+                                size: Size::new(Val::Px(DIGIT_WIDTH), Val::Px(DIGIT_HEIGHT)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        Digit(i),
+                    ))
+                    .with_children(|digit| {
+                        for (j, (position, size)) in segment_layout().into_iter().enumerate() {
+                            digit.spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        position_type: PositionType::Absolute,
+                                        position: UiRect {
+                                            left: Val::Px(DIGIT_WIDTH / 2.0 + position.x - size.x / 2.0),
+                                            top: Val::Px(DIGIT_HEIGHT / 2.0 - position.y - size.y / 2.0),
+                                            ..default()
+                                        },
+                                        size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+                                        ..default()
+                                    },
+                                    background_color: OFF_COLOR.into(),
+                                    ..default()
+                                },
+                                Segment(j),
+                            ));
+                        }
+                    });
+            }
+        });
+}
+
+// Segment centres and sizes relative to the middle of a digit, y up.
+fn segment_layout() -> [(Vec2, Vec2); 7] {
+    let horizontal = Vec2::new(DIGIT_WIDTH, SEGMENT);
+    let vertical = Vec2::new(SEGMENT, DIGIT_HEIGHT / 2.0);
+    let x = DIGIT_WIDTH / 2.0 - SEGMENT / 2.0;
+    let y = DIGIT_HEIGHT / 2.0 - SEGMENT / 2.0;
+    [
+        (Vec2::new(0.0, y), horizontal),
+        (Vec2::new(x, DIGIT_HEIGHT / 4.0), vertical),
+        (Vec2::new(x, -DIGIT_HEIGHT / 4.0), vertical),
+        (Vec2::new(0.0, -y), horizontal),
+        (Vec2::new(-x, -DIGIT_HEIGHT / 4.0), vertical),
+        (Vec2::new(-x, DIGIT_HEIGHT / 4.0), vertical),
+        (Vec2::new(0.0, 0.0), horizontal),
+    ]
+}
+
+fn reset_timer(mut timer: ResMut<GameTimer>) {
+    *timer = GameTimer::default();
+}
+
+// Imported boards start out in game with nothing opened, their timer waits for the first reveal.
+fn start_timer(mut actions: EventReader<PlayerAction>, mut timer: ResMut<GameTimer>) {
+    if actions.iter().any(|action| action.kind == ActionKind::Reveal) && !timer.running {
+        timer.running = true;
+    }
+}
+
+fn stop_timer(mut timer: ResMut<GameTimer>) {
+    timer.running = false;
+}
+
+fn tick_timer(time: Res<Time>, mut timer: ResMut<GameTimer>) {
+    if timer.running {
+        timer.elapsed += time.delta();
+    }
+}
+
+// Segment bit masks for a value, right aligned, clamped to what three digits can show.
+fn display(value: i32) -> [u8; DIGITS] {
+    let mut result = [0; DIGITS];
+    let clamped = value.clamp(-99, 999);
+    let mut rest = clamped.unsigned_abs();
+    for i in (0..DIGITS).rev() {
+        result[i] = DIGIT_SEGMENTS[(rest % 10) as usize];
+        rest /= 10;
+    }
+    if clamped < 0 {
+        result[0] = MINUS_SEGMENTS;
+    }
+    result
+}
+
+fn update_counters(
+    timer: Res<GameTimer>,
+    board: Res<CurrentBoard>,
+    map_info: Res<MapInfo>,
+    counters: Query<(&Counter, &Children)>,
+    digits: Query<(&Digit, &Children)>,
+    mut segments: Query<(&Segment, &mut BackgroundColor)>,
+) {
+    for (counter, counter_children) in counters.iter() {
+        let value = match counter {
+            Counter::Mines => map_info.bomb_count as i32 - board.flags() as i32,
+            Counter::Timer => timer.elapsed.as_secs() as i32,
+        };
+        let shown = display(value);
+        for &digit_entity in counter_children.iter() {
+            let Ok((digit, digit_children)) = digits.get(digit_entity) else { continue };
+            for &segment_entity in digit_children.iter() {
+                let Ok((segment, mut background)) = segments.get_mut(segment_entity) else { continue };
+                let color = if shown[digit.0] & (1 << segment.0) != 0 { ON_COLOR } else { OFF_COLOR };
+                if background.0 != color {
+                    background.0 = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits(values: [usize; DIGITS]) -> [u8; DIGITS] {
+        values.map(|value| DIGIT_SEGMENTS[value])
+    }
+
+    #[test]
+    fn shows_three_digits() {
+        assert_eq!(display(0), digits([0, 0, 0]));
+        assert_eq!(display(7), digits([0, 0, 7]));
+        assert_eq!(display(999), digits([9, 9, 9]));
+    }
+
+    #[test]
+    fn clamps_to_what_fits() {
+        assert_eq!(display(1000), digits([9, 9, 9]));
+        assert_eq!(display(-1000), [MINUS_SEGMENTS, DIGIT_SEGMENTS[9], DIGIT_SEGMENTS[9]]);
+    }
+
+    #[test]
+    fn more_flags_than_mines_go_negative() {
+        assert_eq!(display(-5), [MINUS_SEGMENTS, DIGIT_SEGMENTS[0], DIGIT_SEGMENTS[5]]);
+        assert_eq!(display(-99), [MINUS_SEGMENTS, DIGIT_SEGMENTS[9], DIGIT_SEGMENTS[9]]);
+    }
+}
@@ -6,72 +6,170 @@
 //cargo build --target=x86_64-pc-windows-gnu --release && cargo build --release --target wasm32-unknown-unknown && wasm-bindgen --out-dir ./out/ --web target/wasm32-unknown-unknown/release/saper.wasm
 
 
-use bevy::{prelude::*, window::{PrimaryWindow, WindowResolution}};
+use bevy::{prelude::*, utils::{Duration, Instant}, window::{PrimaryWindow, WindowResolution}};
 //use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use bevy_despawn_with::DespawnAllCommandsExt;
 use bevy_asset_loader::prelude::*;
-
-#[derive(States, PartialEq, Eq, Debug, Clone, Hash, Default)]
+use serde::{Deserialize, Serialize};
+
+mod board;
+use board::{classic_mines, Board, Reveal};
+mod topology;
+use topology::{Neighbourhood, Topology};
+mod seed;
+use seed::{launch_budget, BoardSeed};
+mod solver;
+mod custom;
+mod hud;
+use hud::{GameTimer, HudPlugin};
+mod storage;
+mod stats;
+use stats::{stats_closed, StatsPlugin, StatsSet};
+mod save;
+use save::{SavePlugin, SaveSet};
+mod replay;
+use replay::{not_playing, ActionKind, PlayerAction, ReplayPlugin};
+mod formats;
+use formats::FormatsPlugin;
+mod hint;
+use hint::HintPlugin;
+mod probability;
+use probability::ProbabilityPlugin;
+mod cursor;
+use cursor::CursorPlugin;
+mod touch;
+use touch::TouchPlugin;
+mod view;
+use view::{MousePan, ViewPlugin};
+mod wrap;
+use wrap::WrapPlugin;
+mod multimine;
+use multimine::MultiMinePlugin;
+mod antimine;
+use antimine::{place_with_anti_mines, AntiMinePlugin};
+mod menu;
+use menu::{MenuPlugin, MenuSet, MENU_HEIGHT};
+use custom::{dialog_closed, CustomDialogSet, CustomGamePlugin};
+
+#[derive(States, PartialEq, Eq, Debug, Clone, Hash, Default, Serialize, Deserialize)]
 enum GameState {
     #[default]
     AssetLoading,
     SafeClick,
     InGame,
     GameOver,
+    Won,
 }
 
-const CLICK_AREA_SIZE: f32 = 20.0;
 const TILE_SIZE: f32 = 19.0 * 2.0;
+// biggest window asked for when switching boards, bigger boards get scaled down to it
+const MAX_WINDOW_SIZE: Vec2 = Vec2::new(1280.0, 800.0);
 
 
-const EAZY_BOARD_SIZE: (u8, u8) = (10, 10);
-const EAZY_BOMB_COUNT: u8 = 10;
+const EAZY_BOARD_SIZE: (u16, u16) = (10, 10);
+const EAZY_BOMB_COUNT: u16 = 10;
 
-const MEDIUM_BOARD_SIZE: (u8, u8) = (12, 12);
-const MEDIUM_BOMB_COUNT: u8 = 26;
+const MEDIUM_BOARD_SIZE: (u16, u16) = (12, 12);
+const MEDIUM_BOMB_COUNT: u16 = 26;
 
-const HARD_BOARD_SIZE: (u8, u8) = (15, 15);
-const HARD_BOMB_COUNT: u8 = 40;
+const HARD_BOARD_SIZE: (u16, u16) = (15, 15);
+const HARD_BOMB_COUNT: u16 = 40;
 
-const EXPERT_BOARD_SIZE: (u8, u8) = (16, 30);
-const EXPERT_BOMB_COUNT: u8 = 99;
+const EXPERT_BOARD_SIZE: (u16, u16) = (16, 30);
+const EXPERT_BOMB_COUNT: u16 = 99;
 
-#[derive(Resource)]
-struct Empty { cords: Vec<(u8, u8)> }
+const NO_GUESS_BUDGET: Duration = Duration::from_secs(2);
+
+// difficulties offered in the Game menu
+const PRESETS: [(&str, (u16, u16), u16); 4] = [
+    ("Eazy", EAZY_BOARD_SIZE, EAZY_BOMB_COUNT),
+    ("Medium", MEDIUM_BOARD_SIZE, MEDIUM_BOMB_COUNT),
+    ("Hard", HARD_BOARD_SIZE, HARD_BOMB_COUNT),
+    ("Expert", EXPERT_BOARD_SIZE, EXPERT_BOMB_COUNT),
+];
 
 #[derive(Resource)]
-struct ButtonPositions { 
-    eazy: (f32, f32),
-    medium: (f32, f32),
-    hard: (f32, f32),
-    expert: (f32, f32)
-}
+struct Safe { cords: (u16, u16) }
+
+#[derive(Resource, Deref, DerefMut)]
+struct CurrentBoard(Board);
 
+// regenerate layouts until the solver clears them without guessing, for at most `budget`
+// (`NO_GUESS_BUDGET` unless one is passed in at launch)
 #[derive(Resource)]
-struct Safe { cords: (u8, u8) }
+struct NoGuess {
+    enabled: bool,
+    budget: Duration
+}
 
 #[derive(Resource)]
-struct MapInfo { 
-    board_size: (u8, u8),
-    bomb_count: u8, 
+pub struct MapInfo { 
+    board_size: (u16, u16),
+    bomb_count: u16, 
+    topology: Topology,
+    neighbourhood: Neighbourhood,
+    // mines a single cell may hold
+    max_mines: u8,
+    anti_mines: bool,
 }
 
+impl Default for MapInfo {
+    fn default() -> Self {
+        MapInfo {
+            board_size: EAZY_BOARD_SIZE,
+            bomb_count: EAZY_BOMB_COUNT,
+            topology: Topology::Square,
+            neighbourhood: Neighbourhood::King,
+            max_mines: classic_mines(),
+            anti_mines: false,
+        }
+    }
+}
 
-#[derive(AssetCollection, Resource)]
-struct TileSprites {
+impl MapInfo {
+    fn difficulty(&self) -> String {
+        let name = match PRESETS.iter().find(|(_, size, bombs)| (*size, *bombs) == (self.board_size, self.bomb_count)) {
+            Some((name, _, _)) => name.to_string(),
+            None => format!("Custom {}x{}/{}", self.board_size.1, self.board_size.0, self.bomb_count),
+        };
+        // classic square boards keep the names their stats were saved under
+        let name = match self.topology {
+            Topology::Square => name,
+            topology => format!("{} {}", topology.name(), name),
+        };
+        let name = match self.topology.uses(self.neighbourhood) {
+            Neighbourhood::King => name,
+            neighbourhood => format!("{} {}", neighbourhood.name(), name),
+        };
+        let name = match self.max_mines {
+            1 => name,
+            max_mines => format!("{} (up to {} per cell)", name, max_mines),
+        };
+        if self.anti_mines {
+            format!("{} with anti-mines", name)
+        } else {
+            name
+        }
+    }
 
-    #[asset(path = "sprites/eazy2.png")]
-    eazy: Handle<Image>,
+    // Cells kept free of mines around the first click, itself included.
+    pub fn safe_zone(&self) -> u32 {
+        self.topology.neighbour_count(self.neighbourhood) as u32 + 1
+    }
 
-    #[asset(path = "sprites/medium2.png")]
-    medium: Handle<Image>,
+    // A bigger safe zone or fewer mines per cell may not leave room for all of a custom
+    // board's mines.
+    fn clamp_mines(&mut self) {
+        let (rows, cols) = self.board_size;
+        let room = (rows as u32 * cols as u32).saturating_sub(self.safe_zone()) * self.max_mines as u32;
+        self.bomb_count = self.bomb_count.min(room.min(u16::MAX as u32) as u16);
+    }
+}
 
-    #[asset(path = "sprites/hard2.png")]
-    hard: Handle<Image>,
 
-    #[asset(path = "sprites/expert2.png")]
-    expert: Handle<Image>,
+#[derive(AssetCollection, Resource)]
+struct TileSprites {
 
     #[asset(path = "sprites/tile_unknown2.png")]
     unknown: Handle<Image>,
@@ -118,46 +216,136 @@ struct TileSprites {
     #[asset(path = "sprites/tile_nine2.png")]
     nine: Handle<Image>,
     
+    // anti-mine variant
+    #[asset(path = "sprites/tile_anti_flag2.png")]
+    anti_flag: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_anti_bomb2.png")]
+    anti_bomb: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_anti_exploded2.png")]
+    anti_exploded: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_one2.png")]
+    minus_one: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_two2.png")]
+    minus_two: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_three2.png")]
+    minus_three: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_four2.png")]
+    minus_four: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_five2.png")]
+    minus_five: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_six2.png")]
+    minus_six: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_seven2.png")]
+    minus_seven: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_eight2.png")]
+    minus_eight: Handle<Image>,
+    
+    #[asset(path = "sprites/tile_minus_nine2.png")]
+    minus_nine: Handle<Image>,
+    
+}
+
+#[derive(AssetCollection, Resource)]
+struct Fonts {
+    #[asset(path = "fonts/DejaVuSans-Bold.ttf")]
+    main: Handle<Font>,
 }
 
 #[derive(Debug)]
 #[derive(Reflect)]
 // #[reflect(Component)]
 #[derive(Component)]
+// view of the board cell at (x, y), kept in sync by tile_check
 pub struct Tile { 
-    x: u8,
-    y: u8,
-    num: u8,
+    x: u16,
+    y: u16,
+    num: i8,
     bomb: bool,
     covered: bool,
     flag: bool
 }
 
-#[derive(Component)]
-pub struct Button;
+// Tile entities by board position, and where the board sits in the world.
+#[derive(Resource, Default)]
+pub struct TileIndex {
+    width: u16,
+    height: u16,
+    topology: Topology,
+    // world position of the top left corner of (1, 1)
+    origin: Vec2,
+    entities: Vec<Entity>,
+}
+
+impl TileIndex {
+    pub fn get(&self, x: u16, y: u16) -> Option<Entity> {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            return None;
+        }
+        self.entities.get((y - 1) as usize * self.width as usize + (x - 1) as usize).copied()
+    }
+
+    pub fn center(&self, x: u16, y: u16) -> Vec2 {
+        let shift = self.topology.row_shift(y);
+        self.origin + Vec2::new((x as f32 - 0.5 + shift) * TILE_SIZE, (0.5 - y as f32) * TILE_SIZE)
+    }
+
+    // The row comes first, shifted rows move their columns along with them.
+    pub fn cell_at(&self, world: Vec2) -> Option<(u16, u16)> {
+        let offset = (world - self.origin) / TILE_SIZE;
+        let y = (-offset.y).floor() as i32 + 1;
+        if y < 1 || y > self.height as i32 {
+            return None;
+        }
+        let x = (offset.x - self.topology.row_shift(y as u16)).floor() as i32 + 1;
+        (x >= 1 && x <= self.width as i32).then_some((x as u16, y as u16))
+    }
+}
+
+// Where the mouse points in the world, seen through the camera.
+pub fn cursor_world(window: &Window, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
+    camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)
+}
+
+pub struct GameWon;
+
+pub struct GameLost;
+
+// mouse, keyboard and gamepad input on the board, paused while a dialog is open
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+struct BoardInput;
 
 fn main() {
     App::new()
         .register_type::<Tile>()
-        .insert_resource(Empty{cords: vec!()})
         .insert_resource(Safe{cords: (0,0)})
-        .insert_resource(ButtonPositions{
-            eazy: (0.0, 0.0),
-            medium: (0.0, 0.0),
-            hard: (0.0, 0.0),
-            expert: (0.0, 0.0)
-        })
-        .insert_resource(MapInfo{
-            board_size: EAZY_BOARD_SIZE,
-            bomb_count: EAZY_BOMB_COUNT
+        .insert_resource(CurrentBoard(Board::new(EAZY_BOARD_SIZE.1, EAZY_BOARD_SIZE.0, Topology::Square)))
+        .insert_resource(BoardSeed::from_launch())
+        .insert_resource(NoGuess{
+            enabled: false,
+            budget: launch_budget().unwrap_or(NO_GUESS_BUDGET)
         })
+        .init_resource::<MapInfo>()
+        .init_resource::<TileIndex>()
         .insert_resource(ClearColor(Color::rgb_u8(164, 177, 197)))
         .add_state::<GameState>()
+        .add_event::<GameWon>()
+        .add_event::<GameLost>()
         .add_loading_state(
             LoadingState::new(GameState::AssetLoading)
             .continue_to_state(GameState::SafeClick)
         )
         .add_collection_to_loading_state::<_, TileSprites>(GameState::AssetLoading)
+        .add_collection_to_loading_state::<_, Fonts>(GameState::AssetLoading)
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
@@ -165,35 +353,59 @@ fn main() {
                     primary_window: Some(
                         Window{
                             title: "Minesweeper".to_string(),
-                            resolution: WindowResolution::new(EAZY_BOARD_SIZE.1 as f32 * TILE_SIZE, EAZY_BOARD_SIZE.0 as f32 * TILE_SIZE + TILE_SIZE),
+                            resolution: WindowResolution::new(EAZY_BOARD_SIZE.1 as f32 * TILE_SIZE, EAZY_BOARD_SIZE.0 as f32 * TILE_SIZE + MENU_HEIGHT),
                             canvas: Some("#bevy".to_owned()),
-                            resizable: false,
+                            resizable: true,
                             ..default()
                         }),
                     ..default()
                 })
         )
         //.add_plugin(WorldInspectorPlugin::new())
+        .add_plugin(CustomGamePlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(StatsPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(FormatsPlugin)
+        .add_plugin(HintPlugin)
+        .add_plugin(ProbabilityPlugin)
+        .add_plugin(CursorPlugin)
+        .add_plugin(TouchPlugin)
+        .add_plugin(ViewPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(WrapPlugin)
+        .add_plugin(MultiMinePlugin)
+        .add_plugin(AntiMinePlugin)
+        .configure_set(
+            BoardInput
+                .run_if(dialog_closed)
+                .run_if(stats_closed)
+                .run_if(not_playing)
+                .after(CustomDialogSet)
+                .after(StatsSet)
+                .after(MenuSet)
+        )
         .add_startup_system(spawn_camera)
         .add_systems(
             (
             
             despawn_tiles,
             apply_system_buffers,
+            reroll_seed,
             spawn_tiles,
             
             ).chain().in_schedule(OnEnter(GameState::SafeClick))
         )
         .add_systems(
             (
-            click_switch,
-            apply_system_buffers, 
-            tile_check,
-            apply_system_buffers,
-            zero_check
+            click_switch.in_set(BoardInput),
+            loss_check.run_if(not_playing),
+            win_check.run_if(not_playing)
             ).chain().in_set(OnUpdate(GameState::InGame))
         )
-        .add_system(first_click.in_set(OnUpdate(GameState::SafeClick)))
+        .add_system(tile_check.after(click_switch).after(SaveSet).run_if(resource_exists::<TileSprites>()))
+        .add_system(first_click.in_set(BoardInput).in_set(OnUpdate(GameState::SafeClick)))
         .add_systems(
             (
                 set_bombs,
@@ -201,7 +413,9 @@ fn main() {
             ).chain().in_schedule(OnEnter(GameState::InGame))
         )
         .add_system(game_over.in_schedule(OnEnter(GameState::GameOver)))
-        .add_system(button_click_check)
+        .add_system(game_won.in_schedule(OnEnter(GameState::Won)))
+        .add_system(toggle_no_guess.run_if(dialog_closed))
+        .add_system(update_title)
         .run();
 }
 
@@ -211,10 +425,12 @@ pub fn spawn_camera(
 ) {
     let window = window_query.get_single().unwrap();
     println!("Resolution {:?}", window.resolution);
+    let center = world_size(&MapInfo::default()) / 2.0;
 
     commands.spawn((
         Camera2dBundle {
-            transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0),
+            // the default z keeps everything from 0 up to the camera in view
+            transform: Transform::from_xyz(center.x, center.y, Camera2dBundle::default().transform.translation.z),
             projection: OrthographicProjection { 
                 //scale: -0.2,
                 ..default()
@@ -227,28 +443,39 @@ pub fn spawn_camera(
 
 fn despawn_tiles(mut commands: Commands) {
     commands.despawn_all::<With<Tile>>();
-    commands.despawn_all::<With<Button>>();
 }
 
 fn spawn_tiles(
     mut commands: Commands,
-    window: Query<&Window, With<PrimaryWindow>>,
-    mut buttons: ResMut<ButtonPositions>,
     tile_sprites: Res<TileSprites>,
-    map_info: Res<MapInfo>
+    map_info: Res<MapInfo>,
+    mut board: ResMut<CurrentBoard>,
+    mut index: ResMut<TileIndex>
 ) {
-    let window: &Window = window.get_single().unwrap();
+    let top = world_size(&map_info).y;
+    let margin = map_info.topology.margin() * TILE_SIZE;
     let mut x = 0;
 
     let size_x = map_info.board_size.0;
     let size_y = map_info.board_size.1;
+    board.0 = Board::new(size_y, size_x, map_info.topology)
+        .with_neighbourhood(map_info.neighbourhood)
+        .with_max_mines(map_info.max_mines)
+        .with_anti_mines(map_info.anti_mines);
+    *index = TileIndex {
+        width: size_y,
+        height: size_x,
+        topology: map_info.topology,
+        origin: Vec2::new(margin, top - margin),
+        entities: Vec::new(),
+    };
 
     
     println!("{:?}", std::env::current_exe());
     for i in 0..size_x {
         for j in 0..size_y {
             x += 1;
-            commands.spawn((
+            let tile = commands.spawn((
                 Tile{
                     x: j + 1,
                     y: i + 1,
@@ -259,118 +486,61 @@ fn spawn_tiles(
                 },
                 SpriteBundle{
                     texture: tile_sprites.unknown.clone(),
-                    transform: Transform::from_xyz(
-                        TILE_SIZE * 0.5 + j as f32 * TILE_SIZE,
-                        window.height() - TILE_SIZE - i as f32 * TILE_SIZE - TILE_SIZE * 0.5, 
-                        0.0,
-                    ).with_scale(Vec3::new(2.0, 2.0, 0.0)),
+                    transform: Transform::from_translation(index.center(j + 1, i + 1).extend(0.0))
+                        .with_scale(Vec3::new(2.0, 2.0, 0.0)),
                     ..default()
                 },
                 
                 Name::new("Tile".to_string() + &x.to_string() + " (" + &(j+1).to_string() + ", " + &(i+1).to_string() + ")")
-            ));
+            )).id();
+            index.entities.push(tile);
         }
     }
-    //eazy button
-    commands.spawn(
-        (
-            SpriteBundle {
-            texture: tile_sprites.eazy.clone(),
-            transform: Transform::from_xyz(
-                TILE_SIZE * 0.5,
-                window.height() - TILE_SIZE * 0.5, 
-                0.0,
-            ).with_scale(Vec3::new(2.0, 2.0, 0.0)),
-            ..default()
-            },
-            Button,
-            Name::new("Reset".to_string()),
-        )
-    );
-    buttons.eazy = (TILE_SIZE * 0.5, window.height() - TILE_SIZE * 0.5);
-
-    //medium button
-    commands.spawn(
-        (
-            SpriteBundle {
-            texture: tile_sprites.medium.clone(),
-            transform: Transform::from_xyz(
-                TILE_SIZE * 0.5 + TILE_SIZE,
-                window.height() - TILE_SIZE * 0.5, 
-                0.0,
-            ).with_scale(Vec3::new(2.0, 2.0, 0.0)),
-            ..default()
-            },
-            Button,
-            Name::new("Reset".to_string()),
-        )
-    );
-    buttons.medium = (TILE_SIZE * 0.5 + TILE_SIZE, window.height() - TILE_SIZE * 0.5);
-
-    //hard button
-    commands.spawn(
-        (
-            SpriteBundle {
-            texture: tile_sprites.hard.clone(),
-            transform: Transform::from_xyz(
-                TILE_SIZE * 0.5 + 2.0 * TILE_SIZE,
-                window.height() - TILE_SIZE * 0.5, 
-                0.0,
-            ).with_scale(Vec3::new(2.0, 2.0, 0.0)),
-            ..default()
-            },
-            Button,
-            Name::new("Reset".to_string()),
-        )
-    );
-    buttons.hard = (TILE_SIZE * 0.5 + 2.0 * TILE_SIZE, window.height() - TILE_SIZE * 0.5);
-    
-    //expert button 
-    commands.spawn(
-        (
-            SpriteBundle {
-            texture: tile_sprites.expert.clone(),
-            transform: Transform::from_xyz(
-                TILE_SIZE * 0.5 + 3.0 * TILE_SIZE,
-                window.height() - TILE_SIZE * 0.5, 
-                0.0,
-            ).with_scale(Vec3::new(2.0, 2.0, 0.0)),
-            ..default()
-            },
-            Button,
-            Name::new("Reset".to_string()),
-        )
-    );
-    buttons.expert = (TILE_SIZE * 0.5 + 3.0 * TILE_SIZE, window.height() - TILE_SIZE * 0.5);
+}
 
-    println!("Spawned!");
+fn reroll_seed(mut seed: ResMut<BoardSeed>) {
+    seed.reroll();
+    println!("Seed: {}", seed.seed);
 }
 
+fn toggle_no_guess(
+    keys: Res<Input<KeyCode>>,
+    mut no_guess: ResMut<NoGuess>
+) {
+    if keys.just_pressed(KeyCode::N) {
+        no_guess.enabled = !no_guess.enabled;
+        println!("No guess: {}", no_guess.enabled);
+    }
+}
 
-fn generate_bomb_positions(safe: (u8, u8), map_size: (u8, u8), bomb_count: u8) -> Vec<(u8, u8)> {
-    let mut rng = thread_rng();
-    let mut selected: Vec<(u8, u8)> = Vec::new();
-    let mut safe_zone: Vec<(u8, u8)> = Vec::new();
-    let mut i = bomb_count;
+fn update_title(
+    seed: Res<BoardSeed>,
+    no_guess: Res<NoGuess>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>
+) {
+    if seed.is_changed() || no_guess.is_changed() {
+        let mut title = format!("Minesweeper - seed {}", seed.seed);
+        if no_guess.enabled {
+            title += " - no guess";
+        }
+        window.single_mut().title = title;
+    }
+}
+
+// A cell can be picked again until it holds `max_mines`.
+fn generate_bomb_positions(safe: (u16, u16), map_info: &MapInfo, rng: &mut StdRng) -> Vec<(u16, u16)> {
+    let map_size = map_info.board_size;
+    let mut selected: Vec<(u16, u16)> = Vec::new();
+    let mut safe_zone = map_info.topology.neighbours(map_info.neighbourhood, safe.0, safe.1, map_size.1, map_size.0);
+    let mut i = map_info.bomb_count;
     safe_zone.push(safe);
-    safe_zone.push((safe.0 - 1, safe.1 - 1));
-    safe_zone.push((safe.0, safe.1 - 1));
-    safe_zone.push((safe.0 + 1, safe.1 - 1));
-    safe_zone.push((safe.0 - 1, safe.1));
-    safe_zone.push((safe.0 + 1, safe.1));
-    safe_zone.push((safe.0 - 1, safe.1 + 1));
-    safe_zone.push((safe.0, safe.1 + 1));
-    safe_zone.push((safe.0 + 1, safe.1 + 1));
     
 
     'outer: while i > 0 {
-        let cords: (u8, u8) = (rng.gen_range(1..map_size.1 + 1), rng.gen_range(1..map_size.0 + 1));
+        let cords: (u16, u16) = (rng.gen_range(1..map_size.1 + 1), rng.gen_range(1..map_size.0 + 1));
         //println!("{:?}", selected);
-        for x in selected.iter() {
-            if cords == *x {
-                //println!("{:?}", cords);
-                continue 'outer;
-            }
+        if selected.iter().filter(|x| cords == **x).count() >= map_info.max_mines as usize {
+            continue 'outer;
         }
         for x in safe_zone.iter() {
             if cords == *x {
@@ -381,58 +551,60 @@ fn generate_bomb_positions(safe: (u8, u8), map_size: (u8, u8), bomb_count: u8) -
         i -= 1;
         selected.push(cords);
     }
-    println!("Final: {:?}", selected);
     selected
 }
 
+// Layouts are drawn from the same seeded rng one after another, so a seed still gives the
+// same board unless the time budget runs out first.
+fn generate_no_guess(safe: (u16, u16), map_info: &MapInfo, rng: &mut StdRng, budget: Duration) -> Vec<(u16, u16)> {
+    let start = Instant::now();
+    let deadline = start + budget;
+    let mut attempts = 1;
+    loop {
+        let positions = generate_bomb_positions(safe, map_info, rng);
+        let mut board = Board::new(map_info.board_size.1, map_info.board_size.0, map_info.topology)
+            .with_neighbourhood(map_info.neighbourhood);
+        board.place_bombs(&positions);
+        if solver::solvable(&board, safe, deadline) {
+            println!("No guess board after {} attempts", attempts);
+            return positions;
+        }
+        if start.elapsed() > budget {
+            println!("No guess board not found in {} attempts, guessing may be needed", attempts);
+            return positions;
+        }
+        attempts += 1;
+    }
+}
+
 fn set_bombs(
-    mut tiles: Query<&mut Tile>,
+    tiles: Query<&Tile>,
     safe: Res<Safe>,
-    map_info: Res<MapInfo>
+    seed: Res<BoardSeed>,
+    no_guess: Res<NoGuess>,
+    map_info: Res<MapInfo>,
+    mut board: ResMut<CurrentBoard>
 ) {
     println!("There are {} Entities spawned!", tiles.iter().count());
-
-    let positions = generate_bomb_positions(safe.cords, map_info.board_size, map_info.bomb_count);
-    
-    
-    for mut tile_bomb in tiles.iter_mut() {
-        for cords in positions.iter(){
-            if (tile_bomb.x, tile_bomb.y) == *cords {
-                tile_bomb.bomb = true;
-            }
-        }
+    // restored games come with their bombs already placed
+    if board.bombs() > 0 {
+        return;
     }
 
-    for mut tile in tiles.iter_mut() {
-        for cords in positions.iter() {
-            if !tile.bomb {
-                if (tile.x + 1, tile.y + 1) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x, tile.y + 1) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x - 1, tile.y + 1) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x + 1, tile.y) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x - 1, tile.y) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x + 1, tile.y - 1) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x, tile.y - 1) == *cords {
-                    tile.num += 1;
-                }
-                if (tile.x - 1, tile.y - 1) == *cords {
-                    tile.num += 1;
-                }
-            }
-        }
+    let mut rng = StdRng::seed_from_u64(seed.seed);
+    // the solver only knows classic boards
+    let positions = if no_guess.enabled && solver::supported(&board) {
+        generate_no_guess(safe.cords, &map_info, &mut rng, no_guess.budget)
+    } else {
+        generate_bomb_positions(safe.cords, &map_info, &mut rng)
+    };
+    println!("Final: {:?}", positions);
+    if map_info.anti_mines {
+        place_with_anti_mines(&mut board, &positions);
+    } else {
+        board.place_bombs(&positions);
     }
+    board.reveal(safe.cords.0, safe.cords.1);
     // println!("Set!");
     
 }
@@ -440,67 +612,77 @@ fn set_bombs(
 fn click_switch(
     buttons: Res<Input<MouseButton>>,
     window: Query<&Window>,
-    mut tiles: Query<(&mut Tile, &mut Transform, &mut Handle<Image>)>, 
-    tile_sprites: Res<TileSprites>,
-    mut next_state: ResMut<NextState<GameState>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    index: Res<TileIndex>,
+    drag: Res<MousePan>,
+    mut board: ResMut<CurrentBoard>,
+    mut actions: EventWriter<PlayerAction>
 ) {
-    if buttons.just_pressed(MouseButton::Left) {
-        if let Some(position) = window.get_single().unwrap().cursor_position() {
-        println!("{}", position);
-            for (mut tile, g_transorm, mut image) in tiles.iter_mut() {
-                if tile.covered && !tile.flag {
-                    let x = g_transorm.translation.x;
-                    let y = g_transorm.translation.y;
-                    if (position.x > x - CLICK_AREA_SIZE && position.x < x + CLICK_AREA_SIZE) &&
-                       (position.y < y + CLICK_AREA_SIZE && position.y > y - CLICK_AREA_SIZE) {
-                        tile.covered = false;
-                        if tile.bomb {
-                            *image = tile_sprites.exploded.clone();
-                            next_state.set(GameState::GameOver);
-                        }
-                    }
-                }
-            }
-        }
+    // middle click (on release, dragging with it pans instead), or pressing the second of
+    // left+right while the first one is held
+    let chord = (buttons.just_released(MouseButton::Middle) && !drag.panned) ||
+        (buttons.just_pressed(MouseButton::Left) && buttons.pressed(MouseButton::Right)) ||
+        (buttons.just_pressed(MouseButton::Right) && buttons.pressed(MouseButton::Left));
+
+    let kind = if chord {
+        ActionKind::Chord
+    } else if buttons.just_pressed(MouseButton::Left) {
+        ActionKind::Reveal
+    } else if buttons.just_pressed(MouseButton::Right) {
+        ActionKind::Flag
+    } else {
+        return;
+    };
+
+    let (camera, camera_transform) = camera.single();
+    let world = cursor_world(window.single(), camera, camera_transform);
+    if let Some((x, y)) = world.and_then(|world| index.cell_at(world)) {
+        play(&mut board, kind, x, y, &mut actions);
     }
+}
 
-    if buttons.just_pressed(MouseButton::Right) {
-        if let Some(position) = window.get_single().unwrap().cursor_position() {
-            for (mut tile, g_transorm, mut image) in tiles.iter_mut() {
-                if tile.covered {
-                    let x = g_transorm.translation.x;
-                    let y = g_transorm.translation.y;
-
-                    if (position.x > x - CLICK_AREA_SIZE && position.x < x + CLICK_AREA_SIZE) &&
-                       (position.y < y + CLICK_AREA_SIZE && position.y > y - CLICK_AREA_SIZE) {
-                        if !tile.flag {
-                            tile.flag = true;
-                            println!("Flaga up!");
-                            *image = tile_sprites.flag.clone();
-                        } else {
-                            tile.flag = false;
-                            *image = tile_sprites.unknown.clone();
-                        }
-                    }
-                }
-            }
-        }
+fn loss_check(
+    board: Res<CurrentBoard>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut lost: EventWriter<GameLost>
+) {
+    if board.is_lost() {
+        lost.send(GameLost);
+        next_state.set(GameState::GameOver);
     }
 }
 
 fn tile_check(
     mut tiles: Query<(&mut Tile, &mut Handle<Image>)>,
     tile_sprites: Res<TileSprites>,
-    mut zeros: ResMut<Empty>
+    board: Res<CurrentBoard>
 ) {
-    for (tile1, mut image1) in tiles.iter_mut() {
+    if !board.is_changed() {
+        return;
+    }
+    for (mut tile1, mut image1) in tiles.iter_mut() {
         //println!("{}/{}", tile1.x, tile1.y);
-        if !tile1.covered && !tile1.bomb {
+        let cell = *board.cell(tile1.x, tile1.y).unwrap();
+        tile1.num = cell.num;
+        tile1.bomb = cell.bomb();
+        tile1.covered = cell.covered;
+        tile1.flag = cell.flag();
+
+        if tile1.covered {
+            if cell.flags < 0 {
+                *image1 = tile_sprites.anti_flag.clone();
+            } else if tile1.flag {
+                *image1 = tile_sprites.flag.clone();
+            } else {
+                *image1 = tile_sprites.unknown.clone();
+            }
+        } else if cell.mines < 0 {
+            *image1 = tile_sprites.anti_exploded.clone();
+        } else if tile1.bomb {
+            *image1 = tile_sprites.exploded.clone();
+        } else {
             match tile1.num {
-                0 => {
-                    *image1 = tile_sprites.zero.clone();
-                    zeros.cords.push((tile1.x, tile1.y));
-                },
+                0 => *image1 = tile_sprites.zero.clone(),
                 1 => *image1 = tile_sprites.one.clone(),
                 2 => *image1 = tile_sprites.two.clone(),
                 3 => *image1 = tile_sprites.three.clone(),
@@ -510,122 +692,179 @@ fn tile_check(
                 7 => *image1 = tile_sprites.seven.clone(),
                 8 => *image1 = tile_sprites.eight.clone(),
                 9 => *image1 = tile_sprites.nine.clone(),
-                _ => panic!(),
+                -1 => *image1 = tile_sprites.minus_one.clone(),
+                -2 => *image1 = tile_sprites.minus_two.clone(),
+                -3 => *image1 = tile_sprites.minus_three.clone(),
+                -4 => *image1 = tile_sprites.minus_four.clone(),
+                -5 => *image1 = tile_sprites.minus_five.clone(),
+                -6 => *image1 = tile_sprites.minus_six.clone(),
+                -7 => *image1 = tile_sprites.minus_seven.clone(),
+                -8 => *image1 = tile_sprites.minus_eight.clone(),
+                -9 => *image1 = tile_sprites.minus_nine.clone(),
+                // numbers past 9 either way are written over the empty tile
+                _ => *image1 = tile_sprites.zero.clone(),
             }
         }
     }
 }
 
-fn zero_check(
-    mut zeros: ResMut<Empty>,
-    mut tiles: Query<&mut Tile>
+fn win_check(
+    board: Res<CurrentBoard>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut won: EventWriter<GameWon>
 ) {
-    if !zeros.cords.is_empty(){
-        // println!("Jest jakiś jebaniec!!");
-        for (x, y) in &zeros.cords {
-            for mut tile in tiles.iter_mut(){
-                if tile.covered && tile.x >= x - 1 && tile.x <= x + 1 && tile.y >= y - 1 && tile.y <= y + 1 {
-                    tile.covered = false;
-                }
-            }
+    if !board.is_won() {
+        return;
+    }
+    println!("Won!");
+    won.send(GameWon);
+    next_state.set(GameState::Won);
+}
+
+fn game_won(
+    mut tiles: Query<(&mut Tile, &mut Handle<Image>)>,
+    tile_sprites: Res<TileSprites>,
+    mut board: ResMut<CurrentBoard>
+) {
+    board.flag_bombs();
+    for (mut tile, mut image) in tiles.iter_mut() {
+        if tile.bomb && !tile.flag {
+            tile.flag = true;
+            let anti = board.cell(tile.x, tile.y).unwrap().mines < 0;
+            *image = if anti { tile_sprites.anti_flag.clone() } else { tile_sprites.flag.clone() };
         }
-        zeros.cords.clear();
     }
 }
 
 fn game_over(
     mut tiles: Query<(&Tile, &mut Handle<Image>)>,
-    tile_sprites: Res<TileSprites>
+    tile_sprites: Res<TileSprites>,
+    board: Res<CurrentBoard>
 ) {
     for (tile, mut image) in tiles.iter_mut() {
-        
-        if tile.covered && tile.bomb && !tile.flag{
+        let cell = board.cell(tile.x, tile.y).unwrap();
+        if tile.covered && tile.bomb && !tile.flag && cell.mines < 0 {
+            *image = tile_sprites.anti_bomb.clone();
+        } else if tile.covered && tile.bomb && !tile.flag{
             *image = tile_sprites.bomb.clone();
-        } else if tile.flag && !tile.bomb {
+        } else if tile.flag && cell.flags != cell.mines {
             *image = tile_sprites.flag_cross.clone();
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn first_click(
     buttons: Res<Input<MouseButton>>,
     window: Query<&Window>,
-    mut tiles: Query<(&mut Tile, &Transform)>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    index: Res<TileIndex>,
     mut safe: ResMut<Safe>,
+    mut timer: ResMut<GameTimer>,
     mut next_state: ResMut<NextState<GameState>>,
+    mut actions: EventWriter<PlayerAction>,
 ) {
     if buttons.just_pressed(MouseButton::Left) {
-        if let Some(position) = window.get_single().unwrap().cursor_position() {
-            for (mut tile, g_transorm) in tiles.iter_mut() {
-                let x = g_transorm.translation.x;
-                let y = g_transorm.translation.y;
-                if (position.x > x - CLICK_AREA_SIZE && position.x < x + CLICK_AREA_SIZE) &&
-                   (position.y < y + CLICK_AREA_SIZE && position.y > y - CLICK_AREA_SIZE) {
-                    safe.cords = (tile.x, tile.y);
-                    tile.covered = false;
-                    next_state.set(GameState::InGame);
-
-                }
-            }
+        let (camera, camera_transform) = camera.single();
+        let world = cursor_world(window.single(), camera, camera_transform);
+        if let Some(cords) = world.and_then(|world| index.cell_at(world)) {
+            start_game(cords, &mut safe, &mut timer, &mut next_state, &mut actions);
         }
     }
 }
 
-fn button_click_check(
-    mouse_buttons: Res<Input<MouseButton>>,
-    mut window: Query<&mut Window>,
-    mut camera: Query<(&Camera, &mut Transform)>,
-    mut next_state: ResMut<NextState<GameState>>,
-    buttons: Res<ButtonPositions>,
-    mut map_info: ResMut<MapInfo>
+// Reveal, flag or chord from any input, logged when it changed the board.
+fn play(board: &mut Board, kind: ActionKind, x: u16, y: u16, actions: &mut EventWriter<PlayerAction>) -> bool {
+    let changed = match kind {
+        ActionKind::Reveal => board.reveal(x, y) != Reveal::Nothing,
+        ActionKind::Flag => board.toggle_flag(x, y).is_some(),
+        ActionKind::Chord => board.chord(x, y) != Reveal::Nothing,
+    };
+    if changed {
+        actions.send(PlayerAction { kind, x, y });
+    }
+    changed
+}
+
+// The first reveal, bombs get placed around it on entering InGame.
+fn start_game(
+    cords: (u16, u16),
+    safe: &mut Safe,
+    timer: &mut GameTimer,
+    next_state: &mut NextState<GameState>,
+    actions: &mut EventWriter<PlayerAction>,
 ) {
-    if mouse_buttons.just_pressed(MouseButton::Left) {
-        if let Some(position) = window.get_single().unwrap().cursor_position() {
-            println!("{}", position);
-            if (position.x > buttons.eazy.0 - CLICK_AREA_SIZE && position.x < buttons.eazy.0 + CLICK_AREA_SIZE) &&
-                (position.y < buttons.eazy.1 + CLICK_AREA_SIZE && position.y > buttons.eazy.1 - CLICK_AREA_SIZE) {
-                map_info.board_size = EAZY_BOARD_SIZE;
-                map_info.bomb_count = EAZY_BOMB_COUNT;
-                window.single_mut().resolution = WindowResolution::new(EAZY_BOARD_SIZE.1 as f32 * TILE_SIZE, EAZY_BOARD_SIZE.0 as f32 * TILE_SIZE + TILE_SIZE);
-
-                let (_camera, mut transform) = camera.get_single_mut().unwrap();
-                let window = window.get_single().unwrap();
-                *transform = Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0);
-                next_state.set(GameState::SafeClick);
-            } 
-            else if (position.x > buttons.medium.0 - CLICK_AREA_SIZE && position.x < buttons.medium.0 + CLICK_AREA_SIZE) &&
-                (position.y < buttons.medium.1 + CLICK_AREA_SIZE && position.y > buttons.medium.1 - CLICK_AREA_SIZE) {
-                map_info.board_size = MEDIUM_BOARD_SIZE;
-                map_info.bomb_count = MEDIUM_BOMB_COUNT;
-                window.single_mut().resolution = WindowResolution::new(MEDIUM_BOARD_SIZE.1 as f32 * TILE_SIZE, MEDIUM_BOARD_SIZE.0 as f32 * TILE_SIZE + TILE_SIZE);
-
-                let (_camera, mut transform) = camera.get_single_mut().unwrap();
-                let window = window.get_single().unwrap();
-                *transform = Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0);
-                next_state.set(GameState::SafeClick);
-            }
-            else if (position.x > buttons.hard.0 - CLICK_AREA_SIZE && position.x < buttons.hard.0 + CLICK_AREA_SIZE) &&
-                (position.y < buttons.hard.1 + CLICK_AREA_SIZE && position.y > buttons.hard.1 - CLICK_AREA_SIZE) {
-                map_info.board_size = HARD_BOARD_SIZE;
-                map_info.bomb_count = HARD_BOMB_COUNT;
-                window.single_mut().resolution = WindowResolution::new(HARD_BOARD_SIZE.1 as f32 * TILE_SIZE, HARD_BOARD_SIZE.0 as f32 * TILE_SIZE + TILE_SIZE);
-
-                let (_camera, mut transform) = camera.get_single_mut().unwrap();
-                let window = window.get_single().unwrap();
-                *transform = Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0);
-                next_state.set(GameState::SafeClick);
-            }
-            else if (position.x > buttons.expert.0 - CLICK_AREA_SIZE && position.x < buttons.expert.0 + CLICK_AREA_SIZE) &&
-                (position.y < buttons.expert.1 + CLICK_AREA_SIZE && position.y > buttons.expert.1 - CLICK_AREA_SIZE) {
-                map_info.board_size = EXPERT_BOARD_SIZE;
-                map_info.bomb_count = EXPERT_BOMB_COUNT;
-                window.single_mut().resolution = WindowResolution::new(EXPERT_BOARD_SIZE.1 as f32 * TILE_SIZE, EXPERT_BOARD_SIZE.0 as f32 * TILE_SIZE + TILE_SIZE);
-
-                let (_camera, mut transform) = camera.get_single_mut().unwrap();
-                let window = window.get_single().unwrap();
-                *transform = Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0);
-                next_state.set(GameState::SafeClick);
+    safe.cords = cords;
+    timer.running = true;
+    actions.send(PlayerAction { kind: ActionKind::Reveal, x: cords.0, y: cords.1 });
+    next_state.set(GameState::InGame);
+}
+
+// The board in world units, the camera scales it to whatever the window is.
+pub fn world_size(map_info: &MapInfo) -> Vec2 {
+    let (width, height) = map_info.topology.extent(map_info.board_size.1, map_info.board_size.0);
+    Vec2::new(width * TILE_SIZE, height * TILE_SIZE)
+}
+
+// Asks for a window showing the board at its natural size below the menu bar, shrunk to fit
+// MAX_WINDOW_SIZE.
+pub fn fit_window(window: &mut Window, camera: &mut Transform, map_info: &MapInfo) {
+    let world = world_size(map_info);
+    let shrink = ((MAX_WINDOW_SIZE - Vec2::new(0.0, MENU_HEIGHT)) / world).min_element().min(1.0);
+    window.resolution.set(world.x * shrink, world.y * shrink + MENU_HEIGHT);
+    camera.translation = (world / 2.0).extend(camera.translation.z);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_and_click_give_the_same_layout() {
+        let map_info = MapInfo::default();
+        let safe = (4, 6);
+        for seed in [0, 1, 1234, u64::MAX] {
+            let first = generate_bomb_positions(safe, &map_info, &mut StdRng::seed_from_u64(seed));
+            let second = generate_bomb_positions(safe, &map_info, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(first, second);
+            assert_eq!(first.len(), map_info.bomb_count as usize);
+
+            let (rows, cols) = map_info.board_size;
+            let mut safe_zone = map_info.topology.neighbours(map_info.neighbourhood, safe.0, safe.1, cols, rows);
+            safe_zone.push(safe);
+            assert!(first.iter().all(|mine| !safe_zone.contains(mine)));
+        }
+    }
+
+    // every brick touches exactly the cells its hex neighbours are, and clicks find it again
+    #[test]
+    fn hex_bricks_touch_their_neighbours() {
+        let (width, height) = (5, 4);
+        let index = TileIndex { width, height, topology: Topology::Hex, ..default() };
+        for y in 1..=height {
+            for x in 1..=width {
+                let center = index.center(x, y);
+                for corner in [Vec2::new(-0.45, -0.45), Vec2::new(0.45, 0.45)] {
+                    assert_eq!(index.cell_at(center + corner * TILE_SIZE), Some((x, y)));
+                }
+
+                let mut touching = Vec::new();
+                for ny in 1..=height {
+                    for nx in 1..=width {
+                        let gap = (index.center(nx, ny) - center).abs() / TILE_SIZE;
+                        let beside = ny == y && (gap.x - 1.0).abs() < 0.01;
+                        let across = ny.abs_diff(y) == 1 && gap.x < 0.99;
+                        if beside || across {
+                            touching.push((nx, ny));
+                        }
+                    }
+                }
+                let mut neighbours = Topology::Hex.neighbours(Neighbourhood::King, x, y, width, height);
+                neighbours.sort_by_key(|&(x, y)| (y, x));
+                assert_eq!(touching, neighbours);
             }
         }
+        // the half tile left open at the start of a pushed row belongs to no cell
+        assert_eq!(index.cell_at(index.center(1, 2) - Vec2::new(0.75, 0.0) * TILE_SIZE), None);
     }
-}
\ No newline at end of file
+}
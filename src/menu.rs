@@ -0,0 +1,410 @@
+// The menu bar along the top of the window.
+//
+//     [Game v]      [mines] [face] [timer]      [Stats] [Settings]
+//
+// The face restarts the current board. It looks surprised while a mouse button is held over a
+// covered tile, dies with the game and puts on sunglasses for a win. Game drops down the
+// difficulty presets, the custom game dialog and the board variants: grid shape, which cells
+// count as neighbours, mines a cell may hold and anti-mines (each of those starts a new game),
+// and below them the seed of the board, which the browser has no window title to show in.
+// Settings the toggles that also have keys (N for no guess, O for the probability overlay). A
+// click anywhere else closes an open drop-down without reaching the board.
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+use crate::{
+    custom::{dialog_closed, CustomGame, CustomOpenButton, PANEL_COLOR}, cursor_world, fit_window,
+    hud::{spawn_counter, Counter}, multimine::next_max_mines, probability::ProbabilityOverlay,
+    replay::{not_playing, Playing}, seed::BoardSeed, stats::{stats_closed, StatsOpenButton, StatsScreen},
+    topology::Topology, CurrentBoard, Fonts, GameState, MapInfo, NoGuess, TileIndex, PRESETS,
+};
+
+pub const MENU_HEIGHT: f32 = 38.0;
+const BUTTON_HEIGHT: f32 = 24.0;
+const FACE_SIZE: f32 = 30.0;
+
+const BAR_COLOR: Color = Color::rgb(0.2, 0.23, 0.28);
+const HOVER_COLOR: Color = Color::rgb(0.45, 0.5, 0.6);
+const PRESSED_COLOR: Color = Color::rgb(0.25, 0.28, 0.34);
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum MenuItem {
+    Game,
+    Face,
+    Preset(usize),
+    Custom,
+    Grid,
+    Neighbourhood,
+    MinesPerCell,
+    AntiMines,
+    Stats,
+    Settings,
+    NoGuess,
+    Probabilities,
+}
+
+// Panel dropped down by the Game or Settings button.
+#[derive(Component)]
+struct Dropdown(MenuItem);
+
+#[derive(Component)]
+struct FaceText;
+
+#[derive(Component)]
+struct SettingLabel(MenuItem);
+
+#[derive(Component)]
+struct SeedLabel;
+
+#[derive(Resource, Default)]
+struct Menu {
+    open: Option<MenuItem>,
+}
+
+// board input runs after this so clicks on the menu don't reach the tiles
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct MenuSet;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Menu>()
+            .add_system(spawn_menu.in_schedule(OnExit(GameState::AssetLoading)))
+            .add_system(
+                menu_input
+                    .in_set(MenuSet)
+                    .run_if(dialog_closed)
+                    .run_if(stats_closed)
+                    .run_if(not_playing),
+            )
+            .add_systems((button_colors, update_menu, update_seed, update_face).after(MenuSet));
+    }
+}
+
+fn spawn_button<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    fonts: &Fonts,
+    label: &str,
+    width: f32,
+    item: MenuItem,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn((
+        ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(width), Val::Px(BUTTON_HEIGHT)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: PANEL_COLOR.into(),
+            ..default()
+        },
+        item,
+        Name::new(label.to_string()),
+    ));
+    button.with_children(|parent| {
+        let text = TextBundle::from_section(
+            label,
+            TextStyle { font: fonts.main.clone(), font_size: 12.0, color: Color::WHITE },
+        );
+        if matches!(
+            item,
+            MenuItem::Grid
+                | MenuItem::Neighbourhood
+                | MenuItem::MinesPerCell
+                | MenuItem::AntiMines
+                | MenuItem::NoGuess
+                | MenuItem::Probabilities
+        ) {
+            parent.spawn((text, SettingLabel(item)));
+        } else {
+            parent.spawn(text);
+        }
+    });
+    button
+}
+
+fn group() -> NodeBundle {
+    NodeBundle {
+        style: Style { align_items: AlignItems::Center, gap: Size::all(Val::Px(4.0)), ..default() },
+        ..default()
+    }
+}
+
+fn dropdown(item: MenuItem, left: bool) -> (NodeBundle, Dropdown, Name) {
+    let side = if left { UiRect { left: Val::Px(4.0), ..default() } } else { UiRect { right: Val::Px(4.0), ..default() } };
+    (
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(MENU_HEIGHT), ..side },
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(4.0)),
+                gap: Size::all(Val::Px(4.0)),
+                ..default()
+            },
+            background_color: BAR_COLOR.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Dropdown(item),
+        Name::new(format!("{:?} menu", item)),
+    )
+}
+
+fn spawn_menu(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Px(MENU_HEIGHT)),
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::horizontal(Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: BAR_COLOR.into(),
+                ..default()
+            },
+            Name::new("Menu bar"),
+        ))
+        .with_children(|bar| {
+            bar.spawn(group()).with_children(|left| {
+                spawn_button(left, &fonts, "Game \u{25be}", 52.0, MenuItem::Game);
+            });
+            bar.spawn(group()).with_children(|center| {
+                spawn_counter(center, Counter::Mines);
+                center
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::all(Val::Px(FACE_SIZE)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: PANEL_COLOR.into(),
+                            ..default()
+                        },
+                        MenuItem::Face,
+                        Name::new("Face"),
+                    ))
+                    .with_children(|face| {
+                        face.spawn((
+                            TextBundle::from_section(
+                                ":)",
+                                TextStyle { font: fonts.main.clone(), font_size: 16.0, color: Color::YELLOW },
+                            ),
+                            FaceText,
+                        ));
+                    });
+                spawn_counter(center, Counter::Timer);
+            });
+            bar.spawn(group()).with_children(|right| {
+                spawn_button(right, &fonts, "Stats", 40.0, MenuItem::Stats).insert(StatsOpenButton);
+                spawn_button(right, &fonts, "Settings", 56.0, MenuItem::Settings);
+            });
+        });
+
+    commands.spawn(dropdown(MenuItem::Game, true)).with_children(|panel| {
+        for (i, (name, _, _)) in PRESETS.iter().enumerate() {
+            spawn_button(panel, &fonts, name, 140.0, MenuItem::Preset(i));
+        }
+        spawn_button(panel, &fonts, "Custom...", 140.0, MenuItem::Custom).insert(CustomOpenButton);
+        spawn_button(panel, &fonts, "", 140.0, MenuItem::Grid);
+        spawn_button(panel, &fonts, "", 140.0, MenuItem::Neighbourhood);
+        spawn_button(panel, &fonts, "", 140.0, MenuItem::MinesPerCell);
+        spawn_button(panel, &fonts, "", 140.0, MenuItem::AntiMines);
+        panel.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle { font: fonts.main.clone(), font_size: 10.0, color: Color::WHITE },
+            ),
+            SeedLabel,
+        ));
+    });
+    commands.spawn(dropdown(MenuItem::Settings, false)).with_children(|panel| {
+        spawn_button(panel, &fonts, "", 130.0, MenuItem::NoGuess);
+        spawn_button(panel, &fonts, "", 130.0, MenuItem::Probabilities);
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn menu_input(
+    mut mouse: ResMut<Input<MouseButton>>,
+    items: Query<(&Interaction, &MenuItem), Changed<Interaction>>,
+    mut menu: ResMut<Menu>,
+    mut map_info: ResMut<MapInfo>,
+    mut no_guess: ResMut<NoGuess>,
+    mut overlay: ResMut<ProbabilityOverlay>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let clicked = items.iter().find(|(interaction, _)| **interaction == Interaction::Clicked);
+    let Some((_, &item)) = clicked else {
+        if menu.open.is_some() && mouse.any_just_pressed([MouseButton::Left, MouseButton::Right]) {
+            mouse.clear_just_pressed(MouseButton::Left);
+            mouse.clear_just_pressed(MouseButton::Right);
+            menu.open = None;
+        }
+        return;
+    };
+    mouse.clear_just_pressed(MouseButton::Left);
+
+    match item {
+        MenuItem::Game | MenuItem::Settings => {
+            menu.open = if menu.open == Some(item) { None } else { Some(item) };
+        }
+        // same board size, so the window stays as it is
+        MenuItem::Face => {
+            menu.open = None;
+            next_state.set(GameState::SafeClick);
+        }
+        MenuItem::Preset(i) => {
+            let (_, board_size, bomb_count) = PRESETS[i];
+            map_info.board_size = board_size;
+            map_info.bomb_count = bomb_count;
+            fit_window(&mut window.single_mut(), &mut camera.single_mut(), &map_info);
+            menu.open = None;
+            next_state.set(GameState::SafeClick);
+        }
+        MenuItem::Grid => {
+            map_info.topology = map_info.topology.next();
+            map_info.clamp_mines();
+            fit_window(&mut window.single_mut(), &mut camera.single_mut(), &map_info);
+            menu.open = None;
+            next_state.set(GameState::SafeClick);
+        }
+        // the grid stays the same, only the numbers change
+        MenuItem::Neighbourhood => {
+            map_info.neighbourhood = map_info.neighbourhood.next();
+            map_info.clamp_mines();
+            menu.open = None;
+            next_state.set(GameState::SafeClick);
+        }
+        MenuItem::MinesPerCell => {
+            map_info.max_mines = next_max_mines(map_info.max_mines);
+            map_info.clamp_mines();
+            menu.open = None;
+            next_state.set(GameState::SafeClick);
+        }
+        MenuItem::AntiMines => {
+            map_info.anti_mines = !map_info.anti_mines;
+            menu.open = None;
+            next_state.set(GameState::SafeClick);
+        }
+        // their own plugins open the dialog and the screen
+        MenuItem::Custom | MenuItem::Stats => menu.open = None,
+        MenuItem::NoGuess => no_guess.enabled = !no_guess.enabled,
+        MenuItem::Probabilities => overlay.shown = !overlay.shown,
+    }
+}
+
+fn button_colors(mut buttons: Query<(&Interaction, &MenuItem, &mut BackgroundColor), Changed<Interaction>>) {
+    for (interaction, _, mut background) in buttons.iter_mut() {
+        background.0 = match interaction {
+            Interaction::Clicked => PRESSED_COLOR,
+            Interaction::Hovered => HOVER_COLOR,
+            Interaction::None => PANEL_COLOR,
+        };
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn update_menu(
+    menu: Res<Menu>,
+    map_info: Res<MapInfo>,
+    no_guess: Res<NoGuess>,
+    overlay: Res<ProbabilityOverlay>,
+    mut dropdowns: Query<(&Dropdown, &mut Visibility)>,
+    mut labels: Query<(&SettingLabel, &mut Text)>,
+) {
+    if menu.is_changed() {
+        for (dropdown, mut visibility) in dropdowns.iter_mut() {
+            *visibility = if menu.open == Some(dropdown.0) { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+    if map_info.is_changed() || no_guess.is_changed() || overlay.is_changed() {
+        for (label, mut text) in labels.iter_mut() {
+            text.sections[0].value = match label.0 {
+                MenuItem::Grid => format!("Grid: {}", map_info.topology.name()),
+                // hex cells always have their 6
+                MenuItem::Neighbourhood if map_info.topology == Topology::Hex => "Neighbours: Hex".to_string(),
+                MenuItem::Neighbourhood => format!("Neighbours: {}", map_info.neighbourhood.name()),
+                MenuItem::MinesPerCell => format!("Mines/cell: {}", map_info.max_mines),
+                MenuItem::AntiMines => format!("Anti-mines: {}", on_off(map_info.anti_mines)),
+                MenuItem::NoGuess => format!("No guess: {}", on_off(no_guess.enabled)),
+                _ => format!("Probabilities: {}", on_off(overlay.shown)),
+            };
+        }
+    }
+}
+
+fn update_seed(seed: Res<BoardSeed>, mut labels: Query<(Ref<SeedLabel>, &mut Text)>) {
+    for (label, mut text) in labels.iter_mut() {
+        if seed.is_changed() || label.is_added() {
+            text.sections[0].value = format!("Seed:\n{}", seed.seed);
+        }
+    }
+}
+
+// Whether a mouse button is held with the pointer over a covered tile.
+fn pressing_tile(
+    buttons: &Input<MouseButton>,
+    window: &Window,
+    camera: (&Camera, &GlobalTransform),
+    index: &TileIndex,
+    board: &CurrentBoard,
+) -> bool {
+    if !buttons.any_pressed([MouseButton::Left, MouseButton::Middle, MouseButton::Right]) {
+        return false;
+    }
+    let world = cursor_world(window, camera.0, camera.1);
+    match world.and_then(|world| index.cell_at(world)) {
+        Some((x, y)) => board.cell(x, y).is_some_and(|cell| cell.covered),
+        None => false,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_face(
+    state: Res<State<GameState>>,
+    menu: Res<Menu>,
+    custom: Res<CustomGame>,
+    stats: Res<StatsScreen>,
+    playing: Res<Playing>,
+    buttons: Res<Input<MouseButton>>,
+    window: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    index: Res<TileIndex>,
+    board: Res<CurrentBoard>,
+    mut face: Query<&mut Text, With<FaceText>>,
+) {
+    // only when the press can reach the board
+    let idle = menu.open.is_none() && !custom.open && !stats.open && playing.0.is_none();
+    let face_text = match state.0 {
+        GameState::GameOver => "X(",
+        GameState::Won => "B)",
+        GameState::SafeClick | GameState::InGame
+            if idle && pressing_tile(&buttons, window.single(), camera.single(), &index, &board) =>
+        {
+            ":o"
+        }
+        _ => ":)",
+    };
+    for mut text in face.iter_mut() {
+        if text.sections[0].value != face_text {
+            text.sections[0].value = face_text.to_string();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// Counts on multi-mine boards, where a cell holds up to 3 mines, and on boards whose
+// neighbourhood reaches more than 8 cells.
+//
+// The tile sprites stop at 9, bigger numbers are written over an empty tile. Flags go up by
+// one with every right click and back to none after the most a cell can hold, a flag for more
+// than one mine and a mine cell holding several get their count in the corner.
+
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+
+use crate::{CurrentBoard, Fonts, GameState, TileIndex, TILE_SIZE};
+
+pub const MOST_MINES_PER_CELL: u8 = 3;
+
+const NUMBER_COLOR: Color = Color::rgb(0.45, 0.0, 0.0);
+const COUNT_COLOR: Color = Color::BLACK;
+
+#[derive(Component)]
+struct CountLabel;
+
+pub struct MultiMinePlugin;
+
+impl Plugin for MultiMinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(draw_counts);
+    }
+}
+
+// Mines per cell after `max_mines` when stepping through them from a menu entry.
+pub fn next_max_mines(max_mines: u8) -> u8 {
+    max_mines % MOST_MINES_PER_CELL + 1
+}
+
+fn draw_counts(
+    mut commands: Commands,
+    board: Res<CurrentBoard>,
+    state: Res<State<GameState>>,
+    index: Res<TileIndex>,
+    fonts: Option<Res<Fonts>>,
+) {
+    if !(board.is_changed() || state.is_changed()) {
+        return;
+    }
+    commands.despawn_all::<With<CountLabel>>();
+    let Some(fonts) = fonts else { return };
+
+    let corner = Vec2::new(TILE_SIZE, -TILE_SIZE) * 0.25;
+    for y in 1..=board.height() {
+        for x in 1..=board.width() {
+            let cell = board.cell(x, y).unwrap();
+            // mines under a covered cell only show once the game is lost
+            let mines_shown = !cell.covered || (state.0 == GameState::GameOver && !cell.flag());
+            let (text, color, offset, size) = if !cell.covered && !cell.bomb() && cell.num > 9 {
+                (cell.num.unsigned_abs(), NUMBER_COLOR, Vec2::ZERO, 24.0)
+            } else if cell.covered && cell.flags.unsigned_abs() > 1 {
+                (cell.flags.unsigned_abs(), COUNT_COLOR, corner, 14.0)
+            } else if cell.mines.unsigned_abs() > 1 && mines_shown {
+                (cell.mines.unsigned_abs(), COUNT_COLOR, corner, 14.0)
+            } else {
+                continue;
+            };
+            if index.get(x, y).is_none() {
+                continue;
+            }
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        text.to_string(),
+                        TextStyle { font: fonts.main.clone(), font_size: size, color },
+                    ),
+                    transform: Transform::from_translation((index.center(x, y) + offset).extend(0.5)),
+                    ..default()
+                },
+                CountLabel,
+                Name::new("Mine count"),
+            ));
+        }
+    }
+}
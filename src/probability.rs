@@ -0,0 +1,89 @@
+// O toggles a heat map over the covered tiles with each one's exact chance of hiding a mine,
+// worked out from the visible numbers, the flags and the mines left. Handy for training and
+// for looking back at a lost game.
+
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+
+use crate::{custom::dialog_closed, solver, CurrentBoard, Fonts, GameState, MapInfo, TileIndex, TILE_SIZE};
+
+#[derive(Resource, Default)]
+pub struct ProbabilityOverlay {
+    pub shown: bool,
+}
+
+#[derive(Component)]
+struct OverlayTile;
+
+pub struct ProbabilityPlugin;
+
+impl Plugin for ProbabilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProbabilityOverlay>()
+            .add_systems((toggle_overlay.run_if(dialog_closed), draw_overlay).chain());
+    }
+}
+
+fn toggle_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<ProbabilityOverlay>) {
+    if keys.just_pressed(KeyCode::O) {
+        overlay.shown = !overlay.shown;
+    }
+}
+
+// green for safe through yellow to red for a sure mine
+fn heat(chance: f64) -> Color {
+    let chance = chance.clamp(0.0, 1.0) as f32;
+    Color::rgba((2.0 * chance).min(1.0), (2.0 - 2.0 * chance).min(1.0), 0.0, 0.55)
+}
+
+fn draw_overlay(
+    mut commands: Commands,
+    overlay: Res<ProbabilityOverlay>,
+    board: Res<CurrentBoard>,
+    map_info: Res<MapInfo>,
+    state: Res<State<GameState>>,
+    index: Res<TileIndex>,
+    fonts: Option<Res<Fonts>>,
+) {
+    if !(overlay.is_changed() || board.is_changed() || state.is_changed()) {
+        return;
+    }
+    commands.despawn_all_recursive::<With<OverlayTile>>();
+    let Some(fonts) = fonts else { return };
+    // nothing to go on before the first click
+    if !overlay.shown || matches!(state.0, GameState::AssetLoading | GameState::SafeClick) {
+        return;
+    }
+    let Some(chances) = solver::probabilities(&board, map_info.bomb_count) else {
+        // said once when switched on rather than after every move
+        if overlay.is_changed() {
+            println!("Probabilities can't be worked out for this board");
+        }
+        return;
+    };
+
+    for (&(x, y), &chance) in &chances {
+        if index.get(x, y).is_none() {
+            continue;
+        }
+        commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite { color: heat(chance), custom_size: Some(Vec2::splat(TILE_SIZE)), ..default() },
+                    transform: Transform::from_translation(index.center(x, y).extend(0.6)),
+                    ..default()
+                },
+                OverlayTile,
+            ))
+            .with_children(|parent| {
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        format!("{:.0}", chance * 100.0),
+                        TextStyle { font: fonts.main.clone(), font_size: 13.0, color: Color::BLACK },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    ..default()
+                });
+            });
+    }
+}
@@ -0,0 +1,473 @@
+// Recording and playback of whole games.
+//
+// Every effective reveal, flag and chord is logged with its tile and the time since the first
+// click. When a game ends the log is stored with the seed and mine layout as
+// `last_replay.json`:
+//
+//     {
+//       "version": 1,
+//       "board_size": [16, 30],
+//       "bomb_count": 99,
+//       "seed": 1234,
+//       "topology": "Square",        // Hex or Torus, missing in older replays
+//       "neighbourhood": "King",     // Knight, Manhattan or Cross, missing in older replays
+//       "max_mines": 1,              // mines a cell may hold, missing in older replays
+//       "anti_mines": false,         // missing in older replays
+//       "mines": [[3, 1], [7, 2], ...],  // a cell with more mines is listed once for each
+//       "anti": [[5, 4], ...],       // anti-mines the same way, missing in older replays
+//       "opened": [[1, 1], ...],     // uncovered before the first action, missing in older replays
+//       "flags": [[3, 1, 1], ...],   // x, y and flag count placed before the first action, same
+//       "start_time": 0.0,           // timer at the first action's board, same
+//       "actions": [{"kind": "Reveal", "x": 5, "y": 5, "time": 0.0}, ...]
+//     }
+//
+// A game resumed from a save is recorded from where it was picked up again, with the cells
+// already opened and flagged at that point as its starting board.
+//
+// P plays it back. Space pauses, Left/Right step one action back/forward, Up/Down change the
+// speed, Home seeks to the start, End to the finish and Escape leaves the replay.
+
+use bevy::{prelude::*, utils::Duration};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{classic_mines, Board, Cell}, fit_window, hud::GameTimer, seed::BoardSeed, storage,
+    topology::{Neighbourhood, Topology}, BoardInput, CurrentBoard, GameLost, GameState, GameWon, MapInfo,
+};
+
+const REPLAY_VERSION: u32 = 1;
+const LAST_REPLAY: &str = "last_replay.json";
+const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    Reveal,
+    Flag,
+    Chord,
+}
+
+// Sent by the input systems for every click that changed the board.
+pub struct PlayerAction {
+    pub kind: ActionKind,
+    pub x: u16,
+    pub y: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Action {
+    pub kind: ActionKind,
+    pub x: u16,
+    pub y: u16,
+    // seconds since the first click
+    pub time: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct ActionLog {
+    pub actions: Vec<Action>,
+    // board and timer a resumed game went on from
+    resumed: Option<(Board, f32)>,
+}
+
+impl ActionLog {
+    pub fn resume(&mut self, board: &Board, elapsed: f32) {
+        self.actions.clear();
+        self.resumed = Some((board.clone(), elapsed));
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    version: u32,
+    pub board_size: (u16, u16),
+    pub bomb_count: u16,
+    pub seed: u64,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub neighbourhood: Neighbourhood,
+    #[serde(default = "classic_mines")]
+    pub max_mines: u8,
+    #[serde(default)]
+    pub anti_mines: bool,
+    pub mines: Vec<(u16, u16)>,
+    #[serde(default)]
+    pub anti: Vec<(u16, u16)>,
+    #[serde(default)]
+    pub opened: Vec<(u16, u16)>,
+    #[serde(default)]
+    pub flags: Vec<(u16, u16, i8)>,
+    #[serde(default)]
+    pub start_time: f32,
+    pub actions: Vec<Action>,
+}
+
+impl Replay {
+    pub fn read(name: &str) -> Option<Replay> {
+        let contents = storage::load(name)?;
+        match serde_json::from_str::<Replay>(&contents) {
+            Ok(replay) if replay.version == REPLAY_VERSION => Some(replay),
+            Ok(replay) => {
+                println!("Replay {} has unsupported version {}", name, replay.version);
+                None
+            }
+            Err(e) => {
+                println!("Couldn't read replay {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    pub fn write(&self, name: &str) {
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| storage::save(name, &contents));
+        if let Err(e) = result {
+            println!("Couldn't save replay {}: {}", name, e);
+        }
+    }
+
+    // The board after the first `count` actions.
+    fn board_after(&self, count: usize) -> Board {
+        let mut board = Board::new(self.board_size.1, self.board_size.0, self.topology)
+            .with_neighbourhood(self.neighbourhood)
+            .with_max_mines(self.max_mines)
+            .with_anti_mines(self.anti_mines);
+        board.place_bombs(&self.mines);
+        board.place_anti_mines(&self.anti);
+        for &(x, y) in &self.opened {
+            let Some(&cell) = board.cell(x, y) else { continue };
+            board.set_cell(x, y, Cell { covered: false, ..cell });
+        }
+        for &(x, y, flags) in &self.flags {
+            let Some(&cell) = board.cell(x, y) else { continue };
+            board.set_cell(x, y, Cell { flags, ..cell });
+        }
+        for action in &self.actions[..count] {
+            apply(&mut board, action);
+        }
+        board
+    }
+
+    // Timer reading once the first `count` actions have been played.
+    fn time_after(&self, count: usize) -> f32 {
+        match count {
+            0 => self.start_time,
+            count => self.actions[count - 1].time,
+        }
+    }
+}
+
+pub fn apply(board: &mut Board, action: &Action) {
+    match action.kind {
+        ActionKind::Reveal => {
+            board.reveal(action.x, action.y);
+        }
+        ActionKind::Flag => {
+            board.toggle_flag(action.x, action.y);
+        }
+        ActionKind::Chord => {
+            board.chord(action.x, action.y);
+        }
+    }
+}
+
+pub struct Playback {
+    replay: Replay,
+    // actions applied so far
+    position: usize,
+    time: f32,
+    speed: usize,
+    paused: bool,
+    started: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct Playing(pub Option<Playback>);
+
+pub fn not_playing(playing: Res<Playing>) -> bool {
+    playing.0.is_none()
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActionLog>()
+            .init_resource::<Playing>()
+            .add_event::<PlayerAction>()
+            .add_system(clear_log.in_schedule(OnEnter(GameState::SafeClick)))
+            .add_systems((record_actions, save_replay).chain().distributive_run_if(not_playing))
+            .add_systems((drive_playback, start_playback.in_set(BoardInput)).chain());
+    }
+}
+
+fn clear_log(mut log: ResMut<ActionLog>) {
+    log.actions.clear();
+    log.resumed = None;
+}
+
+fn record_actions(
+    mut actions: EventReader<PlayerAction>,
+    mut log: ResMut<ActionLog>,
+    timer: Res<GameTimer>,
+) {
+    for action in actions.iter() {
+        log.actions.push(Action {
+            kind: action.kind,
+            x: action.x,
+            y: action.y,
+            time: timer.elapsed.as_secs_f32(),
+        });
+    }
+}
+
+fn save_replay(
+    mut won: EventReader<GameWon>,
+    mut lost: EventReader<GameLost>,
+    log: Res<ActionLog>,
+    board: Res<CurrentBoard>,
+    map_info: Res<MapInfo>,
+    seed: Res<BoardSeed>,
+) {
+    if won.iter().count() + lost.iter().count() == 0 {
+        return;
+    }
+    let (mut mines, mut anti) = (Vec::new(), Vec::new());
+    for y in 1..=board.height() {
+        for x in 1..=board.width() {
+            let count = board.cell(x, y).unwrap().mines;
+            let list = if count < 0 { &mut anti } else { &mut mines };
+            list.extend(std::iter::repeat_n((x, y), count.unsigned_abs() as usize));
+        }
+    }
+    let (mut opened, mut flags, mut start_time) = (Vec::new(), Vec::new(), 0.0);
+    if let Some((start, elapsed)) = &log.resumed {
+        for y in 1..=start.height() {
+            for x in 1..=start.width() {
+                let cell = start.cell(x, y).unwrap();
+                if !cell.covered {
+                    opened.push((x, y));
+                } else if cell.flag() {
+                    flags.push((x, y, cell.flags));
+                }
+            }
+        }
+        start_time = *elapsed;
+    }
+    Replay {
+        version: REPLAY_VERSION,
+        board_size: map_info.board_size,
+        bomb_count: map_info.bomb_count,
+        seed: seed.seed,
+        topology: board.topology(),
+        neighbourhood: board.neighbourhood(),
+        max_mines: board.max_mines(),
+        anti_mines: board.anti_mines(),
+        mines,
+        anti,
+        opened,
+        flags,
+        start_time,
+        actions: log.actions.clone(),
+    }
+    .write(LAST_REPLAY);
+}
+
+fn start_playback(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut playing: ResMut<Playing>,
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if state.0 == GameState::AssetLoading || playing.0.is_some() || !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+    let Some(replay) = Replay::read(LAST_REPLAY) else {
+        println!("No replay to play");
+        return;
+    };
+    map_info.board_size = replay.board_size;
+    map_info.bomb_count = replay.bomb_count;
+    map_info.topology = replay.topology;
+    map_info.neighbourhood = replay.neighbourhood;
+    map_info.max_mines = replay.max_mines;
+    map_info.anti_mines = replay.anti_mines;
+    fit_window(&mut window.single_mut(), &mut camera.single_mut(), &map_info);
+    playing.0 = Some(Playback { time: replay.start_time, replay, position: 0, speed: 2, paused: false, started: false });
+    next_state.set(GameState::SafeClick);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn drive_playback(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut playing: ResMut<Playing>,
+    mut board: ResMut<CurrentBoard>,
+    mut timer: ResMut<GameTimer>,
+    mut window: Query<&mut Window>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(playback) = playing.0.as_mut() else { return };
+
+    // the tiles get spawned by entering SafeClick, after that the board is ours
+    if !playback.started {
+        if state.0 == GameState::SafeClick {
+            playback.started = true;
+            board.0 = playback.replay.board_after(0);
+            next_state.set(GameState::InGame);
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        playing.0 = None;
+        next_state.set(GameState::SafeClick);
+        return;
+    }
+
+    let actions = playback.replay.actions.len();
+    let mut seek = None;
+    if keys.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        playback.speed = (playback.speed + 1).min(SPEEDS.len() - 1);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        playback.speed = playback.speed.saturating_sub(1);
+    }
+    if keys.just_pressed(KeyCode::Right) && playback.position < actions {
+        playback.paused = true;
+        seek = Some(playback.position + 1);
+    }
+    if keys.just_pressed(KeyCode::Left) && playback.position > 0 {
+        playback.paused = true;
+        seek = Some(playback.position - 1);
+    }
+    if keys.just_pressed(KeyCode::Home) {
+        seek = Some(0);
+    }
+    if keys.just_pressed(KeyCode::End) {
+        seek = Some(actions);
+    }
+
+    if let Some(position) = seek {
+        playback.position = position;
+        playback.time = playback.replay.time_after(position);
+        board.0 = playback.replay.board_after(position);
+    } else if !playback.paused {
+        playback.time += time.delta_seconds() * SPEEDS[playback.speed];
+        while playback.position < actions && playback.replay.actions[playback.position].time <= playback.time {
+            apply(&mut board.0, &playback.replay.actions[playback.position]);
+            playback.position += 1;
+        }
+    }
+
+    // times come from a file, a broken one mustn't take the game down
+    timer.elapsed = Duration::try_from_secs_f32(playback.time).unwrap_or_default();
+    timer.running = false;
+
+    let target = if board.is_lost() {
+        GameState::GameOver
+    } else if board.is_won() {
+        GameState::Won
+    } else {
+        GameState::InGame
+    };
+    if state.0 != target {
+        next_state.set(target);
+    }
+
+    window.single_mut().title = format!(
+        "Minesweeper - replay {}/{} x{}{}",
+        playback.position,
+        actions,
+        SPEEDS[playback.speed],
+        if playback.paused { " (paused)" } else { "" }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4x3 with a mine in the corner, (2, 2) is the only number next to it
+    fn replay(actions: &[(ActionKind, u16, u16, f32)]) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            board_size: (3, 4),
+            bomb_count: 1,
+            seed: 0,
+            topology: Topology::Square,
+            neighbourhood: Neighbourhood::King,
+            max_mines: classic_mines(),
+            anti_mines: false,
+            mines: vec![(1, 1)],
+            anti: Vec::new(),
+            opened: Vec::new(),
+            flags: Vec::new(),
+            start_time: 0.0,
+            actions: actions.iter().map(|&(kind, x, y, time)| Action { kind, x, y, time }).collect(),
+        }
+    }
+
+    fn covered(board: &Board) -> usize {
+        (1..=board.height())
+            .flat_map(|y| (1..=board.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| board.cell(x, y).unwrap().covered)
+            .count()
+    }
+
+    #[test]
+    fn plays_reveal_flag_and_chord() {
+        let replay = replay(&[(ActionKind::Reveal, 2, 2, 0.0), (ActionKind::Flag, 1, 1, 1.5), (ActionKind::Chord, 2, 2, 3.0)]);
+        assert_eq!(covered(&replay.board_after(0)), 12);
+
+        let board = replay.board_after(1);
+        assert_eq!(covered(&board), 11);
+        assert_eq!(board.cell(2, 2).unwrap().num, 1);
+
+        let board = replay.board_after(2);
+        assert_eq!(board.cell(1, 1).unwrap().flags, 1);
+        assert!(!board.is_won());
+
+        let board = replay.board_after(3);
+        assert_eq!(covered(&board), 1);
+        assert!(board.is_won());
+        assert_eq!(replay.time_after(3), 3.0);
+    }
+
+    #[test]
+    fn seeks_back_to_where_a_resumed_game_started() {
+        let mut replay = replay(&[(ActionKind::Chord, 2, 2, 5.0)]);
+        replay.opened = vec![(2, 2)];
+        replay.flags = vec![(1, 1, 1)];
+        replay.start_time = 4.0;
+
+        let start = replay.board_after(0);
+        assert!(!start.cell(2, 2).unwrap().covered);
+        assert_eq!(start.cell(1, 1).unwrap().flags, 1);
+        assert_eq!(covered(&start), 11);
+        assert_eq!(replay.time_after(0), 4.0);
+
+        assert!(replay.board_after(1).is_won());
+        assert_eq!(replay.time_after(1), 5.0);
+        // seeking back gives the resumed board again, not an empty one
+        assert_eq!(covered(&replay.board_after(0)), 11);
+    }
+
+    #[test]
+    fn older_replays_start_from_an_empty_board() {
+        let json = r#"{"version": 1, "board_size": [3, 4], "bomb_count": 1, "seed": 0, "mines": [[1, 1]],
+            "actions": [{"kind": "Reveal", "x": 4, "y": 3, "time": 0.0}]}"#;
+        let replay: Replay = serde_json::from_str(json).unwrap();
+        assert_eq!(replay.time_after(0), 0.0);
+        assert_eq!(covered(&replay.board_after(0)), 12);
+        assert!(replay.board_after(1).is_won());
+    }
+}
@@ -0,0 +1,412 @@
+// Saving and resuming games.
+//
+// A save is a JSON document:
+//
+//     {
+//       "version": 2,
+//       "board_size": [16, 30],      // rows, columns (same order as `MapInfo::board_size`)
+//       "bomb_count": 99,
+//       "seed": 1234,
+//       "topology": "Square",        // Hex or Torus, missing in older saves
+//       "neighbourhood": "King",     // Knight, Manhattan or Cross, missing in older saves
+//       "max_mines": 1,              // mines a cell may hold, missing in older saves
+//       "anti_mines": false,         // missing in older saves
+//       "elapsed": 12.5,             // seconds on the timer
+//       "state": "InGame",           // SafeClick, InGame, GameOver or Won
+//       "tiles": [{"x": 1, "y": 1, "num": 0, "mines": 0, "covered": true, "flags": 0}, ...]
+//     }
+//
+// `tiles` holds every tile, x/y are 1-based like on `Tile`, anti-mines and anti-flags are
+// negative counts. Version 1 saves may have `bomb` and `flag` booleans on the tiles instead,
+// they get turned into counts when read. Saves bigger than a custom game allows, without
+// mines or with more than fit are turned down, as is one missing tiles. Imported boards can be
+// smaller than a custom game, so any size from 1 is taken.
+//
+// The game in progress is written to `autosave.json` when the window closes (after every move
+// in the browser, which has no exit event) and picked up again on launch. 1-3 load a slot,
+// Shift+1-3 save into it.
+
+use bevy::{app::AppExit, prelude::*, utils::Duration, window::WindowCloseRequested};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{classic_mines, Board, Cell}, custom::MAX_SIZE, fit_window, hud::GameTimer, multimine::MOST_MINES_PER_CELL,
+    replay::{ActionLog, Playing}, seed::BoardSeed, storage, topology::{Neighbourhood, Topology}, BoardInput, CurrentBoard,
+    GameState, MapInfo,
+};
+
+const SAVE_VERSION: u32 = 2;
+const AUTOSAVE: &str = "autosave.json";
+const SLOT_KEYS: [KeyCode; 3] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedTile {
+    pub x: u16,
+    pub y: u16,
+    pub num: i8,
+    // negative for anti-mines
+    #[serde(default)]
+    pub mines: i8,
+    pub covered: bool,
+    // negative for anti-flags
+    #[serde(default)]
+    pub flags: i8,
+    // what version 1 saves had before the counts
+    #[serde(default, skip_serializing)]
+    bomb: bool,
+    #[serde(default, skip_serializing)]
+    flag: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedGame {
+    version: u32,
+    pub board_size: (u16, u16),
+    pub bomb_count: u16,
+    pub seed: u64,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub neighbourhood: Neighbourhood,
+    #[serde(default = "classic_mines")]
+    pub max_mines: u8,
+    #[serde(default)]
+    pub anti_mines: bool,
+    pub elapsed: f32,
+    pub state: GameState,
+    pub tiles: Vec<SavedTile>,
+}
+
+impl SavedGame {
+    pub fn capture(board: &Board, map_info: &MapInfo, seed: u64, elapsed: Duration, state: GameState) -> Self {
+        let mut tiles = Vec::new();
+        for y in 1..=board.height() {
+            for x in 1..=board.width() {
+                let cell = board.cell(x, y).unwrap();
+                tiles.push(SavedTile {
+                    x,
+                    y,
+                    num: cell.num,
+                    mines: cell.mines,
+                    covered: cell.covered,
+                    flags: cell.flags,
+                    bomb: false,
+                    flag: false,
+                });
+            }
+        }
+        SavedGame {
+            version: SAVE_VERSION,
+            board_size: map_info.board_size,
+            bomb_count: map_info.bomb_count,
+            seed,
+            topology: board.topology(),
+            neighbourhood: board.neighbourhood(),
+            max_mines: board.max_mines(),
+            anti_mines: board.anti_mines(),
+            elapsed: elapsed.as_secs_f32(),
+            state,
+            tiles,
+        }
+    }
+
+    pub fn board(&self) -> Board {
+        let mut board = Board::new(self.board_size.1, self.board_size.0, self.topology)
+            .with_neighbourhood(self.neighbourhood)
+            .with_max_mines(self.max_mines)
+            .with_anti_mines(self.anti_mines);
+        for tile in &self.tiles {
+            board.set_cell(
+                tile.x,
+                tile.y,
+                Cell {
+                    num: tile.num,
+                    mines: tile.mines,
+                    covered: tile.covered,
+                    flags: tile.flags,
+                },
+            );
+        }
+        board
+    }
+
+    // Version 1 saves come from before cells held mine and flag counts.
+    fn migrate(mut self) -> SavedGame {
+        for tile in &mut self.tiles {
+            if tile.bomb {
+                tile.mines = 1;
+            }
+            if tile.flag {
+                tile.flags = 1;
+            }
+        }
+        self.version = SAVE_VERSION;
+        self
+    }
+
+    // Hand edited or corrupt saves are loaded on every launch, so nothing is trusted.
+    fn check(&self) -> Result<(), String> {
+        let (rows, cols) = self.board_size;
+        if !(1..=MAX_SIZE).contains(&rows) || !(1..=MAX_SIZE).contains(&cols) {
+            return Err(format!("board is {}x{}, at most {}x{} can be loaded", cols, rows, MAX_SIZE, MAX_SIZE));
+        }
+        if self.state == GameState::AssetLoading {
+            return Err("saved while loading".to_string());
+        }
+        if !(1..=MOST_MINES_PER_CELL).contains(&self.max_mines) {
+            return Err(format!("{} mines per cell", self.max_mines));
+        }
+        let cells = rows as u32 * cols as u32;
+        let outside = |tile: &SavedTile| !(1..=cols).contains(&tile.x) || !(1..=rows).contains(&tile.y);
+        if self.tiles.len() as u32 != cells || self.tiles.iter().any(outside) {
+            return Err(format!("tiles don't cover the {}x{} board", cols, rows));
+        }
+        // mines are only placed after the first click, which keeps its safe zone clear
+        let info = MapInfo {
+            board_size: self.board_size,
+            bomb_count: self.bomb_count,
+            topology: self.topology,
+            neighbourhood: self.neighbourhood,
+            max_mines: self.max_mines,
+            anti_mines: self.anti_mines,
+        };
+        let room = if self.state == GameState::SafeClick { cells.saturating_sub(info.safe_zone()) } else { cells };
+        let most_mines = room * self.max_mines as u32;
+        if self.bomb_count == 0 || self.bomb_count as u32 > most_mines {
+            return Err(format!("{} mines, must be 1-{}", self.bomb_count, most_mines));
+        }
+        Ok(())
+    }
+
+    fn parse(contents: &str) -> Result<SavedGame, String> {
+        let save = serde_json::from_str::<SavedGame>(contents).map_err(|e| e.to_string())?;
+        let save = match save.version {
+            SAVE_VERSION => save,
+            1 => save.migrate(),
+            version => return Err(format!("unsupported version {}", version)),
+        };
+        save.check()?;
+        Ok(save)
+    }
+
+    pub fn read(name: &str) -> Option<SavedGame> {
+        let contents = storage::load(name)?;
+        match SavedGame::parse(&contents) {
+            Ok(save) => Some(save),
+            Err(e) => {
+                println!("Couldn't read save {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    pub fn write(&self, name: &str) {
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| storage::save(name, &contents));
+        match result {
+            Ok(()) => println!("Saved {}", name),
+            Err(e) => println!("Couldn't save {}: {}", name, e),
+        }
+    }
+}
+
+// Save waiting to be put on the freshly spawned board once `SafeClick` has been entered.
+#[derive(Resource, Default)]
+pub struct PendingRestore(pub Option<SavedGame>);
+
+// tiles are synced after this so a restored board shows up before the saved state is entered
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct SaveSet;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingRestore>()
+            .add_system(load_autosave.in_schedule(OnExit(GameState::AssetLoading)))
+            .add_system(restore_pending.in_set(OnUpdate(GameState::SafeClick)).in_set(SaveSet))
+            .add_system(save_slots.in_set(SaveSet).in_set(BoardInput))
+            .add_system(autosave.in_base_set(CoreSet::PostUpdate));
+    }
+}
+
+// Resizes for the saved board and restarts, `restore_pending` finishes the job.
+pub fn start_restore(
+    save: SavedGame,
+    map_info: &mut MapInfo,
+    window: &mut Window,
+    camera: &mut Transform,
+    pending: &mut PendingRestore,
+    next_state: &mut NextState<GameState>,
+) {
+    map_info.board_size = save.board_size;
+    map_info.bomb_count = save.bomb_count;
+    map_info.topology = save.topology;
+    map_info.neighbourhood = save.neighbourhood;
+    map_info.max_mines = save.max_mines;
+    map_info.anti_mines = save.anti_mines;
+    fit_window(window, camera, map_info);
+    pending.0 = Some(save);
+    next_state.set(GameState::SafeClick);
+}
+
+pub fn load_autosave(
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut pending: ResMut<PendingRestore>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(save) = SavedGame::read(AUTOSAVE) {
+        println!("Resuming autosave");
+        start_restore(save, &mut map_info, &mut window.single_mut(), &mut camera.single_mut(), &mut pending, &mut next_state);
+    }
+}
+
+fn restore_pending(
+    mut pending: ResMut<PendingRestore>,
+    mut board: ResMut<CurrentBoard>,
+    mut seed: ResMut<BoardSeed>,
+    mut timer: ResMut<GameTimer>,
+    mut log: ResMut<ActionLog>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(save) = pending.0.take() else { return };
+    board.0 = save.board();
+    seed.seed = save.seed;
+    // hand edited saves may hold anything here
+    timer.elapsed = Duration::try_from_secs_f32(save.elapsed).unwrap_or_default();
+    // nothing opened yet means an imported board waiting for its first click
+    timer.running = save.state == GameState::InGame && save.tiles.iter().any(|tile| !tile.covered);
+    // the replay goes on from here rather than from an empty board
+    log.resume(&board, timer.elapsed.as_secs_f32());
+    if save.state != GameState::SafeClick {
+        next_state.set(save.state);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_slots(
+    keys: Res<Input<KeyCode>>,
+    board: Res<CurrentBoard>,
+    seed: Res<BoardSeed>,
+    timer: Res<GameTimer>,
+    state: Res<State<GameState>>,
+    mut map_info: ResMut<MapInfo>,
+    mut window: Query<&mut Window>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut pending: ResMut<PendingRestore>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if state.0 == GameState::AssetLoading {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    for (i, key) in SLOT_KEYS.into_iter().enumerate() {
+        if !keys.just_pressed(key) {
+            continue;
+        }
+        let name = format!("slot{}.json", i + 1);
+        if shift {
+            SavedGame::capture(&board, &map_info, seed.seed, timer.elapsed, state.0.clone()).write(&name);
+        } else if let Some(save) = SavedGame::read(&name) {
+            start_restore(save, &mut map_info, &mut window.single_mut(), &mut camera.single_mut(), &mut pending, &mut next_state);
+        }
+    }
+}
+
+// Only a game still being played is worth resuming, anything else clears the autosave.
+fn write_autosave(board: &Board, map_info: &MapInfo, seed: &BoardSeed, timer: &GameTimer, state: &GameState) {
+    if *state == GameState::InGame {
+        SavedGame::capture(board, map_info, seed.seed, timer.elapsed, state.clone()).write(AUTOSAVE);
+    } else if storage::load(AUTOSAVE).is_some() {
+        storage::remove(AUTOSAVE);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn autosave(
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut exits: EventReader<AppExit>,
+    board: Res<CurrentBoard>,
+    map_info: Res<MapInfo>,
+    seed: Res<BoardSeed>,
+    timer: Res<GameTimer>,
+    state: Res<State<GameState>>,
+    playing: Res<Playing>,
+) {
+    // a replay being watched isn't the player's game
+    if state.0 == GameState::AssetLoading || playing.0.is_some() {
+        return;
+    }
+    let exiting = close_requests.iter().count() + exits.iter().count() > 0;
+    let save = if cfg!(target_arch = "wasm32") { board.is_changed() || state.is_changed() } else { exiting };
+    if save {
+        write_autosave(&board, &map_info, &seed, &timer, &state.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION_1: &str = r#"{
+        "version": 1,
+        "board_size": [1, 2],
+        "bomb_count": 1,
+        "seed": 7,
+        "elapsed": 3.5,
+        "state": "InGame",
+        "tiles": [
+            {"x": 1, "y": 1, "num": 0, "covered": true, "bomb": true, "flag": true},
+            {"x": 2, "y": 1, "num": 1, "covered": false}
+        ]
+    }"#;
+
+    #[test]
+    fn version_1_saves_get_counts() {
+        let save = SavedGame::parse(VERSION_1).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        let board = save.board();
+        assert_eq!(board.cell(1, 1).unwrap().mines, 1);
+        assert_eq!(board.cell(1, 1).unwrap().flags, 1);
+        assert_eq!(board.cell(2, 1).unwrap().mines, 0);
+        assert!(!board.cell(2, 1).unwrap().covered);
+    }
+
+    #[test]
+    fn captured_saves_read_back_the_same() {
+        let map_info = MapInfo::default();
+        let (rows, cols) = map_info.board_size;
+        let mut board = Board::new(cols, rows, map_info.topology);
+        board.place_bombs(&[(1, 1), (4, 2)]);
+        board.reveal(9, 9);
+        board.toggle_flag(1, 1);
+        let save = SavedGame::capture(&board, &map_info, 42, Duration::from_secs(5), GameState::InGame);
+        let read = SavedGame::parse(&serde_json::to_string(&save).unwrap()).unwrap();
+        assert_eq!(read.seed, 42);
+        assert_eq!(read.elapsed, 5.0);
+        assert_eq!(read.state, GameState::InGame);
+        let again = read.board();
+        for y in 1..=rows {
+            for x in 1..=cols {
+                assert_eq!(again.cell(x, y), board.cell(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn turns_down_broken_saves() {
+        let edit = |from: &str, to: &str| SavedGame::parse(&VERSION_1.replace(from, to));
+        assert!(edit("[1, 2]", "[0, 0]").is_err());
+        assert!(edit("[1, 2]", "[65535, 65535]").is_err());
+        assert!(edit("[1, 2]", "[2, 2]").is_err());
+        assert!(edit("\"bomb_count\": 1", "\"bomb_count\": 0").is_err());
+        assert!(edit("\"bomb_count\": 1", "\"bomb_count\": 3").is_err());
+        assert!(edit("\"InGame\"", "\"AssetLoading\"").is_err());
+        assert!(edit("\"x\": 2", "\"x\": 3").is_err());
+        // a first click on a 2x1 board leaves no room outside its safe zone
+        assert!(edit("\"InGame\"", "\"SafeClick\"").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use bevy::{prelude::Resource, utils::Duration};
+use rand::{thread_rng, Rng};
+
+// Seed the mine layout is generated from. `fixed` is set when the seed was passed in
+// (`--seed N` natively, `?seed=N` in the browser) so every new game reuses it.
+#[derive(Resource, Debug)]
+pub struct BoardSeed {
+    pub seed: u64,
+    pub fixed: bool,
+}
+
+impl BoardSeed {
+    pub fn from_launch() -> Self {
+        match launch_value("seed").and_then(|seed| seed.parse().ok()) {
+            Some(seed) => BoardSeed { seed, fixed: true },
+            None => BoardSeed { seed: thread_rng().gen(), fixed: false },
+        }
+    }
+
+    pub fn reroll(&mut self) {
+        if !self.fixed {
+            self.seed = thread_rng().gen();
+        }
+    }
+}
+
+// Seconds the no guess generator may spend on a board, passed in next to the seed
+// (`--budget 5` natively, `?budget=5` in the browser).
+pub fn launch_budget() -> Option<Duration> {
+    let budget = launch_value("budget")?;
+    match budget.parse().ok().and_then(|secs| Duration::try_from_secs_f32(secs).ok()).filter(|budget| !budget.is_zero()) {
+        Some(budget) => Some(budget),
+        None => {
+            println!("Ignoring no guess budget {}, expected seconds", budget);
+            None
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn launch_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let flag = format!("--{}", name);
+    args.iter().position(|arg| *arg == flag).and_then(|i| args.get(i + 1)).cloned()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn launch_value(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    let prefix = format!("{}=", name);
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(prefix.as_str()))
+        .map(str::to_string)
+}
@@ -0,0 +1,592 @@
+// Deductions from what the player can see: uncovered numbers and flags. Bomb positions of
+// covered cells are never looked at. The rules take every cell to hold one mine or none, so
+// boards with more mines per cell or with anti-mines get no constraints at all.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use bevy::utils::Instant;
+
+use crate::board::Board;
+
+// Steps one frontier component may take to enumerate before it's given up on.
+const MAX_SEARCH_STEPS: u32 = 1_000_000;
+// how often an enumeration with a deadline looks at the clock
+const DEADLINE_CHECK_STEPS: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finding {
+    Safe(u16, u16),
+    Mine(u16, u16),
+}
+
+// Why a finding holds, tiles given are the numbers the rule looked at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    SingleCell((u16, u16)),
+    // all covered tiles of the first number also touch the second
+    Subset((u16, u16), (u16, u16)),
+    // number of mine arrangements that fit every number on the border
+    Enumeration(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deduction {
+    pub finding: Finding,
+    pub rule: Rule,
+}
+
+impl Deduction {
+    pub fn explain(&self, board: &Board) -> String {
+        let (what, (x, y)) = match self.finding {
+            Finding::Safe(x, y) => ("safe", (x, y)),
+            Finding::Mine(x, y) => ("a mine", (x, y)),
+        };
+        let num = |(x, y): (u16, u16)| board.cell(x, y).map_or(0, |cell| cell.num);
+        let reason = match (self.rule, self.finding) {
+            (Rule::SingleCell(at), Finding::Safe(..)) => {
+                format!("the {} at {:?} already has all its mines flagged", num(at), at)
+            }
+            (Rule::SingleCell(at), Finding::Mine(..)) => {
+                format!("the {} at {:?} has only as many covered tiles as missing mines", num(at), at)
+            }
+            (Rule::Subset(small, big), finding) => format!(
+                "the covered tiles of the {} at {:?} all touch the {} at {:?} too, so its other tiles are all {}",
+                num(small),
+                small,
+                num(big),
+                big,
+                if matches!(finding, Finding::Safe(..)) { "safe" } else { "mines" }
+            ),
+            (Rule::Enumeration(count), _) => {
+                format!("it is {} in all {} ways the mines can fit the numbers around it", what, count)
+            }
+        };
+        format!("({}, {}) is {}: {}", x, y, what, reason)
+    }
+}
+
+// `mines` of the covered, unflagged `cells` are bombs, as told by the number at `origin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub origin: (u16, u16),
+    pub cells: Vec<(u16, u16)>,
+    pub mines: u8,
+}
+
+pub fn supported(board: &Board) -> bool {
+    board.max_mines() == 1 && !board.anti_mines()
+}
+
+pub fn constraints(board: &Board) -> Vec<Constraint> {
+    let mut result: Vec<Constraint> = Vec::new();
+    if !supported(board) {
+        return result;
+    }
+    for y in 1..=board.height() {
+        for x in 1..=board.width() {
+            let cell = board.cell(x, y).unwrap();
+            if cell.covered || cell.bomb() {
+                continue;
+            }
+            let mut cells = Vec::new();
+            let mut flags = 0;
+            for (nx, ny) in board.neighbours(x, y) {
+                let neighbour = board.cell(nx, ny).unwrap();
+                // a mine that went off counts as flagged, it matters when analysing a lost game
+                if neighbour.flag() || (neighbour.bomb() && !neighbour.covered) {
+                    flags += 1;
+                } else if neighbour.covered {
+                    cells.push((nx, ny));
+                }
+            }
+            if !cells.is_empty() && cell.num >= flags {
+                let mines = (cell.num - flags) as u8;
+                if !result.iter().any(|other| other.cells == cells && other.mines == mines) {
+                    result.push(Constraint { origin: (x, y), cells, mines });
+                }
+            }
+        }
+    }
+    result
+}
+
+fn push(deductions: &mut Vec<Deduction>, finding: Finding, rule: Rule) {
+    if !deductions.iter().any(|deduction| deduction.finding == finding) {
+        deductions.push(Deduction { finding, rule });
+    }
+}
+
+// A number with all its mines flagged is safe around, one with as many covered cells as
+// missing mines is all mines.
+pub fn single_cell(constraints: &[Constraint]) -> Vec<Deduction> {
+    let mut deductions = Vec::new();
+    for constraint in constraints {
+        let rule = Rule::SingleCell(constraint.origin);
+        if constraint.mines == 0 {
+            for &(x, y) in &constraint.cells {
+                push(&mut deductions, Finding::Safe(x, y), rule);
+            }
+        } else if constraint.mines as usize == constraint.cells.len() {
+            for &(x, y) in &constraint.cells {
+                push(&mut deductions, Finding::Mine(x, y), rule);
+            }
+        }
+    }
+    deductions
+}
+
+// When the cells of one constraint are a subset of another, the leftover cells hold the
+// difference of their mines.
+pub fn subset(constraints: &[Constraint]) -> Vec<Deduction> {
+    let mut deductions = Vec::new();
+    for small in constraints {
+        for big in constraints {
+            if small.cells.len() >= big.cells.len() || small.mines > big.mines {
+                continue;
+            }
+            if !small.cells.iter().all(|cell| big.cells.contains(cell)) {
+                continue;
+            }
+            let rule = Rule::Subset(small.origin, big.origin);
+            let rest: Vec<(u16, u16)> = big.cells.iter().filter(|cell| !small.cells.contains(cell)).copied().collect();
+            let mines = big.mines - small.mines;
+            if mines == 0 {
+                for &(x, y) in &rest {
+                    push(&mut deductions, Finding::Safe(x, y), rule);
+                }
+            } else if mines as usize == rest.len() {
+                for &(x, y) in &rest {
+                    push(&mut deductions, Finding::Mine(x, y), rule);
+                }
+            }
+        }
+    }
+    deductions
+}
+
+// Frontier cells linked through shared constraints and every way to place mines on them.
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub cells: Vec<(u16, u16)>,
+    // mines in the arrangement -> (arrangements, how many of them have a mine on each cell)
+    pub solutions: BTreeMap<usize, (u64, Vec<u64>)>,
+    // false when the search ran out of steps, `solutions` is then partial
+    pub complete: bool,
+}
+
+impl Component {
+    pub fn arrangements(&self) -> u64 {
+        self.solutions.values().map(|(count, _)| count).sum()
+    }
+}
+
+struct Search<'a> {
+    constraints: Vec<&'a Constraint>,
+    // constraint indices touching each cell
+    touching: Vec<Vec<usize>>,
+    placed: Vec<u8>,
+    open: Vec<usize>,
+    mines: Vec<bool>,
+    solutions: BTreeMap<usize, (u64, Vec<u64>)>,
+    steps: u32,
+    deadline: Option<Instant>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, index: usize) {
+        if self.steps == MAX_SEARCH_STEPS {
+            return;
+        }
+        self.steps += 1;
+        // running past the deadline gives up the same way as running out of steps
+        if self.steps.is_multiple_of(DEADLINE_CHECK_STEPS) && self.deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            self.steps = MAX_SEARCH_STEPS;
+            return;
+        }
+        if index == self.mines.len() {
+            let count = self.mines.iter().filter(|&&mine| mine).count();
+            let cells = self.mines.len();
+            let (total, tallies) = self.solutions.entry(count).or_insert_with(|| (0, vec![0; cells]));
+            *total += 1;
+            for (tally, &mine) in tallies.iter_mut().zip(&self.mines) {
+                *tally += mine as u64;
+            }
+            return;
+        }
+        for mine in [false, true] {
+            let fits = self.touching[index].iter().all(|&i| {
+                let placed = self.placed[i] + mine as u8;
+                let open = self.open[i] - 1;
+                placed <= self.constraints[i].mines && placed as usize + open >= self.constraints[i].mines as usize
+            });
+            if !fits {
+                continue;
+            }
+            for &i in &self.touching[index] {
+                self.placed[i] += mine as u8;
+                self.open[i] -= 1;
+            }
+            self.mines[index] = mine;
+            self.run(index + 1);
+            for &i in &self.touching[index] {
+                self.placed[i] -= mine as u8;
+                self.open[i] += 1;
+            }
+        }
+        self.mines[index] = false;
+    }
+}
+
+// Splits the frontier into independent components and enumerates them, the ones left when
+// `deadline` passes come back incomplete.
+pub fn components(constraints: &[Constraint], deadline: Option<Instant>) -> Vec<Component> {
+    let mut touching: HashMap<(u16, u16), Vec<usize>> = HashMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        for &cell in &constraint.cells {
+            touching.entry(cell).or_default().push(i);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for constraint in constraints {
+        for &start in &constraint.cells {
+            if seen.contains(&start) {
+                continue;
+            }
+            // breadth first so neighbouring cells get decided one after another
+            let mut cells = Vec::new();
+            let mut members = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            seen.insert(start);
+            while let Some(cell) = queue.pop_front() {
+                cells.push(cell);
+                for &i in &touching[&cell] {
+                    if !members.contains(&i) {
+                        members.push(i);
+                    }
+                    for &next in &constraints[i].cells {
+                        if seen.insert(next) {
+                            queue.push_back(next);
+                        }
+                    }
+                }
+            }
+
+            let local: HashMap<usize, usize> = members.iter().enumerate().map(|(local, &i)| (i, local)).collect();
+            let mut search = Search {
+                constraints: members.iter().map(|&i| &constraints[i]).collect(),
+                touching: cells.iter().map(|cell| touching[cell].iter().map(|i| local[i]).collect()).collect(),
+                placed: vec![0; members.len()],
+                open: members.iter().map(|&i| constraints[i].cells.len()).collect(),
+                mines: vec![false; cells.len()],
+                solutions: BTreeMap::new(),
+                steps: 0,
+                deadline,
+            };
+            search.run(0);
+            let complete = search.steps < MAX_SEARCH_STEPS;
+            result.push(Component { cells, solutions: search.solutions, complete });
+        }
+    }
+    result
+}
+
+// A frontier cell that is a mine in none or in all of the arrangements is decided.
+pub fn enumeration(constraints: &[Constraint], deadline: Option<Instant>) -> Vec<Deduction> {
+    let mut deductions = Vec::new();
+    for component in components(constraints, deadline) {
+        let arrangements = component.arrangements();
+        if !component.complete || arrangements == 0 {
+            continue;
+        }
+        let rule = Rule::Enumeration(arrangements);
+        for (i, &(x, y)) in component.cells.iter().enumerate() {
+            let mines: u64 = component.solutions.values().map(|(_, tallies)| tallies[i]).sum();
+            if mines == 0 {
+                push(&mut deductions, Finding::Safe(x, y), rule);
+            } else if mines == arrangements {
+                push(&mut deductions, Finding::Mine(x, y), rule);
+            }
+        }
+    }
+    deductions
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+    result
+}
+
+// Chance of a mine under every covered, unflagged cell when the board holds `mines` in total.
+// Every arrangement of the frontier is weighted by the ways to put the remaining mines on the
+// covered cells away from it. None when a component was too big to enumerate, the flags
+// contradict the numbers or the board isn't a classic one.
+pub fn probabilities(board: &Board, mines: u16) -> Option<HashMap<(u16, u16), f64>> {
+    if !supported(board) {
+        return None;
+    }
+    let components = components(&constraints(board), None);
+    if components.iter().any(|component| !component.complete) {
+        return None;
+    }
+
+    let mut known = 0;
+    let mut interior = Vec::new();
+    let frontier: HashSet<(u16, u16)> = components.iter().flat_map(|component| component.cells.iter().copied()).collect();
+    for y in 1..=board.height() {
+        for x in 1..=board.width() {
+            let cell = board.cell(x, y).unwrap();
+            if cell.flag() || (cell.bomb() && !cell.covered) {
+                known += 1;
+            } else if cell.covered && !frontier.contains(&(x, y)) {
+                interior.push((x, y));
+            }
+        }
+    }
+    let remaining = (mines as usize).saturating_sub(known);
+
+    // ln of the ways to put the mines left over by `frontier_mines` on the interior, relative to
+    // the biggest so the weights stay representable
+    let mut ln_factorial = vec![0.0; interior.len() + 1];
+    for n in 1..ln_factorial.len() {
+        ln_factorial[n] = ln_factorial[n - 1] + (n as f64).ln();
+    }
+    let ln_choose = |frontier_mines: usize| {
+        let k = remaining.checked_sub(frontier_mines).filter(|&k| k <= interior.len())?;
+        Some(ln_factorial[interior.len()] - ln_factorial[k] - ln_factorial[interior.len() - k])
+    };
+    let top = (0..=remaining).filter_map(ln_choose).fold(f64::NEG_INFINITY, f64::max);
+    let weight = |frontier_mines: usize| ln_choose(frontier_mines).map_or(0.0, |ln| (ln - top).exp());
+
+    // arrangements per mine count, each component scaled down to its own maximum since only
+    // ratios matter
+    let distributions: Vec<Vec<f64>> = components
+        .iter()
+        .map(|component| {
+            let mut distribution = vec![0.0; component.cells.len() + 1];
+            for (&count, &(arrangements, _)) in &component.solutions {
+                distribution[count] = arrangements as f64;
+            }
+            let max = distribution.iter().copied().fold(0.0, f64::max).max(1.0);
+            distribution.iter().map(|count| count / max).collect()
+        })
+        .collect();
+    let all = distributions.iter().fold(vec![1.0], |total, distribution| convolve(&total, distribution));
+    let total: f64 = all.iter().enumerate().map(|(count, ways)| ways * weight(count)).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut result = HashMap::new();
+    for (c, component) in components.iter().enumerate() {
+        let others = distributions
+            .iter()
+            .enumerate()
+            .filter(|&(other, _)| other != c)
+            .fold(vec![1.0], |total, (_, distribution)| convolve(&total, distribution));
+        let scale = component.solutions.values().map(|&(count, _)| count as f64).fold(0.0, f64::max).max(1.0);
+        for (i, &cell) in component.cells.iter().enumerate() {
+            let mut chance = 0.0;
+            for (&count, (_, tallies)) in &component.solutions {
+                let rest: f64 = others.iter().enumerate().map(|(extra, ways)| ways * weight(count + extra)).sum();
+                chance += tallies[i] as f64 / scale * rest;
+            }
+            result.insert(cell, chance / total);
+        }
+    }
+    if !interior.is_empty() {
+        let expected: f64 = all
+            .iter()
+            .enumerate()
+            .map(|(count, ways)| ways * weight(count) * remaining.saturating_sub(count) as f64)
+            .sum();
+        let chance = expected / total / interior.len() as f64;
+        for cell in interior {
+            result.insert(cell, chance);
+        }
+    }
+    Some(result)
+}
+
+// Cheapest rule first, later ones only run when the earlier found nothing.
+pub fn deductions(board: &Board, deadline: Option<Instant>) -> Vec<Deduction> {
+    let constraints = constraints(board);
+    for rule in [single_cell, subset] {
+        let deductions = rule(&constraints);
+        if !deductions.is_empty() {
+            return deductions;
+        }
+    }
+    enumeration(&constraints, deadline)
+}
+
+pub fn find(board: &Board, deadline: Option<Instant>) -> Vec<Finding> {
+    deductions(board, deadline).into_iter().map(|deduction| deduction.finding).collect()
+}
+
+// Constraints only hold covered, unflagged cells, so any deduction is a move still to make.
+pub fn hint(board: &Board) -> Option<Deduction> {
+    deductions(board, None).into_iter().next()
+}
+
+/// Plays a copy of the board from the first click using only deductions, true if it gets
+/// cleared without ever having to guess. Gives up with false once `deadline` has passed.
+pub fn solvable(board: &Board, safe: (u16, u16), deadline: Instant) -> bool {
+    let mut board = board.clone();
+    board.reveal(safe.0, safe.1);
+    loop {
+        if board.is_won() {
+            return true;
+        }
+        if Instant::now() > deadline {
+            return false;
+        }
+        let findings = find(&board, Some(deadline));
+        if findings.is_empty() {
+            return false;
+        }
+        for finding in findings {
+            match finding {
+                Finding::Safe(x, y) => {
+                    board.reveal(x, y);
+                }
+                Finding::Mine(x, y) => {
+                    if board.cell(x, y).is_some_and(|cell| !cell.flag()) {
+                        board.toggle_flag(x, y);
+                    }
+                }
+            }
+        }
+        if board.is_lost() {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::Duration;
+
+    use super::*;
+    use crate::topology::Topology;
+
+    fn board(width: u16, height: u16, mines: &[(u16, u16)]) -> Board {
+        let mut board = Board::new(width, height, Topology::Square);
+        board.place_bombs(mines);
+        board
+    }
+
+    fn later() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    #[test]
+    fn single_cell_finds_mines() {
+        let mut board = board(3, 1, &[(3, 1)]);
+        board.reveal(1, 1);
+        let deductions = single_cell(&constraints(&board));
+        assert_eq!(deductions, vec![Deduction { finding: Finding::Mine(3, 1), rule: Rule::SingleCell((2, 1)) }]);
+    }
+
+    #[test]
+    fn single_cell_finds_safe_cells() {
+        let mut board = board(3, 2, &[(1, 1)]);
+        board.reveal(2, 1);
+        board.toggle_flag(1, 1);
+        let findings: Vec<Finding> = single_cell(&constraints(&board)).iter().map(|d| d.finding).collect();
+        assert_eq!(findings, vec![Finding::Safe(3, 1), Finding::Safe(1, 2), Finding::Safe(2, 2), Finding::Safe(3, 2)]);
+    }
+
+    #[test]
+    fn solvable_plays_out_deductions() {
+        assert!(solvable(&board(5, 5, &[(5, 5)]), (1, 1), later()));
+        // the mine needs a deduction before the last safe cell can be opened
+        assert!(solvable(&board(3, 3, &[(1, 1), (1, 3)]), (3, 2), later()));
+    }
+
+    #[test]
+    fn a_coin_flip_is_not_solvable() {
+        assert!(!solvable(&board(2, 3, &[(1, 3)]), (1, 1), later()));
+    }
+
+    #[test]
+    fn solvable_gives_up_at_the_deadline() {
+        let passed = Instant::now().checked_sub(Duration::from_secs(1)).unwrap();
+        assert!(!solvable(&board(3, 3, &[(1, 1), (1, 3)]), (3, 2), passed));
+    }
+
+    // after opening (3, 2) the left column reads 1, 2, 1 over three covered cells
+    fn one_two_one() -> Board {
+        let mut board = board(3, 3, &[(1, 1), (1, 3)]);
+        board.reveal(3, 2);
+        board
+    }
+
+    #[test]
+    fn subset_finds_what_single_cells_miss() {
+        let constraints = constraints(&one_two_one());
+        assert!(single_cell(&constraints).is_empty());
+        let findings: Vec<Finding> = subset(&constraints).iter().map(|d| d.finding).collect();
+        assert!(findings.contains(&Finding::Mine(1, 3)));
+        assert!(findings.contains(&Finding::Mine(1, 1)));
+        assert!(!findings.contains(&Finding::Safe(1, 1)));
+    }
+
+    #[test]
+    fn enumeration_decides_every_cell_of_a_unique_arrangement() {
+        let deductions = enumeration(&constraints(&one_two_one()), None);
+        let findings: Vec<Finding> = deductions.iter().map(|d| d.finding).collect();
+        assert_eq!(findings, vec![Finding::Mine(1, 1), Finding::Safe(1, 2), Finding::Mine(1, 3)]);
+        assert!(deductions.iter().all(|d| d.rule == Rule::Enumeration(1)));
+    }
+
+    #[test]
+    fn enumeration_leaves_a_coin_flip_open() {
+        let mut board = board(2, 3, &[(1, 3)]);
+        board.reveal(1, 1);
+        let constraints = constraints(&board);
+        assert!(enumeration(&constraints, None).is_empty());
+        let components = components(&constraints, None);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].arrangements(), 2);
+        assert!(components[0].complete);
+    }
+
+    #[test]
+    fn hints_come_from_the_cheapest_rule() {
+        let deduction = hint(&one_two_one()).unwrap();
+        assert!(matches!(deduction.rule, Rule::Subset(..)));
+    }
+
+    #[test]
+    fn probabilities_split_a_coin_flip() {
+        let mut board = board(2, 3, &[(1, 3)]);
+        board.reveal(1, 1);
+        let chances = probabilities(&board, 1).unwrap();
+        assert_eq!(chances.len(), 2);
+        assert!((chances[&(1, 3)] - 0.5).abs() < 1e-9);
+        assert!((chances[&(2, 3)] - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn probabilities_spread_mines_away_from_the_frontier() {
+        let board = board(3, 3, &[(1, 1), (2, 2)]);
+        let chances = probabilities(&board, 2).unwrap();
+        assert_eq!(chances.len(), 9);
+        assert!(chances.values().all(|chance| (chance - 2.0 / 9.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn probabilities_follow_deductions() {
+        let chances = probabilities(&one_two_one(), 2).unwrap();
+        assert!((chances[&(1, 1)] - 1.0).abs() < 1e-9);
+        assert!(chances[&(1, 2)].abs() < 1e-9);
+        assert!((chances[&(1, 3)] - 1.0).abs() < 1e-9);
+    }
+}
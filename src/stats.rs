@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{custom::PANEL_COLOR, hud::GameTimer, storage, CurrentBoard, Fonts, GameLost, GameState, GameWon, MapInfo};
+
+const STATS_FILE: &str = "stats.json";
+// bump when the layout of `Statistics` changes, older files are then started over
+const STATS_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct DifficultyStats {
+    pub played: u32,
+    pub won: u32,
+    pub streak: u32,
+    pub best_streak: u32,
+    // seconds
+    pub best_time: Option<f32>,
+    pub best_3bv_per_second: Option<f32>,
+}
+
+// Stored as JSON: `{"version": 1, "difficulties": {"Eazy": {...}, "Custom 20x20/60": {...}}}`.
+#[derive(Resource, Serialize, Deserialize, Debug)]
+pub struct Statistics {
+    version: u32,
+    pub difficulties: BTreeMap<String, DifficultyStats>,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Statistics { version: STATS_VERSION, difficulties: BTreeMap::new() }
+    }
+}
+
+impl Statistics {
+    pub fn load() -> Self {
+        let Some(contents) = storage::load(STATS_FILE) else { return Statistics::default() };
+        match serde_json::from_str::<Statistics>(&contents) {
+            Ok(stats) if stats.version == STATS_VERSION => stats,
+            Ok(stats) => {
+                println!("Statistics version {} not supported, starting over", stats.version);
+                Statistics::default()
+            }
+            Err(e) => {
+                println!("Couldn't read statistics: {}", e);
+                Statistics::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| storage::save(STATS_FILE, &contents));
+        if let Err(e) = result {
+            println!("Couldn't save statistics: {}", e);
+        }
+    }
+
+    pub fn record(&mut self, difficulty: String, won: bool, seconds: f32, three_bv: u32) {
+        let stats = self.difficulties.entry(difficulty).or_default();
+        stats.played += 1;
+        if !won {
+            stats.streak = 0;
+            return;
+        }
+        stats.won += 1;
+        stats.streak += 1;
+        stats.best_streak = stats.best_streak.max(stats.streak);
+        if stats.best_time.is_none_or(|best| seconds < best) {
+            stats.best_time = Some(seconds);
+        }
+        if seconds > 0.0 {
+            let speed = three_bv as f32 / seconds;
+            if stats.best_3bv_per_second.is_none_or(|best| speed > best) {
+                stats.best_3bv_per_second = Some(speed);
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct StatsScreen {
+    pub open: bool,
+}
+
+// board input runs after this so the click closing the screen doesn't reach the tiles
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct StatsSet;
+
+// put on the menu entry that opens it
+#[derive(Component)]
+pub struct StatsOpenButton;
+
+#[derive(Component)]
+struct StatsPanel;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Statistics::load())
+            .init_resource::<StatsScreen>()
+            .add_system(record_win)
+            .add_system(record_loss)
+            .add_system(toggle_screen.in_set(StatsSet));
+    }
+}
+
+pub fn stats_closed(screen: Res<StatsScreen>) -> bool {
+    !screen.open
+}
+
+fn record_win(
+    mut won: EventReader<GameWon>,
+    mut stats: ResMut<Statistics>,
+    timer: Res<GameTimer>,
+    board: Res<CurrentBoard>,
+    map_info: Res<MapInfo>,
+) {
+    for _ in won.iter() {
+        stats.record(map_info.difficulty(), true, timer.elapsed.as_secs_f32(), board.three_bv());
+        stats.save();
+    }
+}
+
+fn record_loss(
+    mut lost: EventReader<GameLost>,
+    mut stats: ResMut<Statistics>,
+    timer: Res<GameTimer>,
+    board: Res<CurrentBoard>,
+    map_info: Res<MapInfo>,
+) {
+    for _ in lost.iter() {
+        stats.record(map_info.difficulty(), false, timer.elapsed.as_secs_f32(), board.three_bv());
+        stats.save();
+    }
+}
+
+fn format_row(name: &str, stats: &DifficultyStats) -> String {
+    let percent = (stats.won * 100).checked_div(stats.played).unwrap_or(0);
+    let best_time = stats.best_time.map_or("-".to_string(), |time| format!("{:.1}s", time));
+    let speed = stats.best_3bv_per_second.map_or("-".to_string(), |speed| format!("{:.2}", speed));
+    format!(
+        "{}\n  won {}/{} ({}%)  streak {} (best {})\n  best time {}  3BV/s {}",
+        name, stats.won, stats.played, percent, stats.streak, stats.best_streak, best_time, speed
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn toggle_screen(
+    mut commands: Commands,
+    mut mouse: ResMut<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    open_button: Query<&Interaction, (Changed<Interaction>, With<StatsOpenButton>)>,
+    mut screen: ResMut<StatsScreen>,
+    stats: Res<Statistics>,
+    fonts: Res<Fonts>,
+    state: Res<State<GameState>>,
+) {
+    if state.0 == GameState::AssetLoading {
+        return;
+    }
+    if screen.open {
+        if mouse.just_pressed(MouseButton::Left) || mouse.just_pressed(MouseButton::Right) || keys.just_pressed(KeyCode::Escape) {
+            mouse.clear_just_pressed(MouseButton::Left);
+            mouse.clear_just_pressed(MouseButton::Right);
+            screen.open = false;
+            commands.despawn_all_recursive::<With<StatsPanel>>();
+        }
+        return;
+    }
+    if !open_button.iter().any(|interaction| *interaction == Interaction::Clicked) {
+        return;
+    }
+    mouse.clear_just_pressed(MouseButton::Left);
+    screen.open = true;
+
+    let text = if stats.difficulties.is_empty() {
+        "No games played yet".to_string()
+    } else {
+        stats
+            .difficulties
+            .iter()
+            .map(|(name, stats)| format_row(name, stats))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                ..default()
+            },
+            StatsPanel,
+            Name::new("Statistics screen"),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style { padding: UiRect::all(Val::Px(10.0)), ..default() },
+                    background_color: PANEL_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|panel| {
+                    panel.spawn(TextBundle::from_section(
+                        text,
+                        TextStyle { font: fonts.main.clone(), font_size: 14.0, color: Color::WHITE },
+                    ));
+                });
+        });
+}
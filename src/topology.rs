@@ -0,0 +1,269 @@
+// How the cells of a board connect and sit next to each other on screen.
+//
+// Square: the classic grid, every cell touches the 8 around it.
+// Hex: every other row (the even ones, 1-based) is pushed right by half a tile, so the tiles
+// lie like bricks and each one touches 6 others: two beside it, two above and two below. That
+// is a hexagonal grid drawn with the usual sprites, and clicks land on the brick under them.
+// Torus: the square grid with its edges wrapping around, left to right and top to bottom, so
+// every cell touches 8 others. A border of faded copies of the opposite edges is drawn around
+// it to show what lies across.
+//
+// On the two square grids the 8 around a cell can be swapped for another `Neighbourhood`: the
+// knight's moves, everything within two steps along rows and columns, or a cross two cells
+// long each way. Hex cells always have their 6.
+//
+// Coordinates are 1-based (x = column, y = row) like everywhere else.
+
+use serde::{Deserialize, Serialize};
+
+const KING: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+const KNIGHT: [(i32, i32); 8] = [(-1, -2), (1, -2), (-2, -1), (2, -1), (-2, 1), (2, 1), (-1, 2), (1, 2)];
+// everything up to two steps away along rows and columns
+const MANHATTAN: [(i32, i32); 12] = [
+    (0, -2),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-2, 0),
+    (-1, 0),
+    (1, 0),
+    (2, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (0, 2),
+];
+// straight up, down and to the sides, two cells each way
+const CROSS: [(i32, i32); 8] = [(0, -2), (0, -1), (-2, 0), (-1, 0), (1, 0), (2, 0), (0, 1), (0, 2)];
+// rows that stay in place and rows pushed right see the rows around them from different sides
+const HEX_ODD_ROW: [(i32, i32); 6] = [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+const HEX_EVEN_ROW: [(i32, i32); 6] = [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+// Which cells around a square grid cell count towards its number.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Neighbourhood {
+    #[default]
+    King,
+    Knight,
+    Manhattan,
+    Cross,
+}
+
+impl Neighbourhood {
+    pub fn name(self) -> &'static str {
+        match self {
+            Neighbourhood::King => "King",
+            Neighbourhood::Knight => "Knight",
+            Neighbourhood::Manhattan => "Manhattan",
+            Neighbourhood::Cross => "Cross",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Neighbourhood> {
+        match name.to_ascii_lowercase().as_str() {
+            "king" => Some(Neighbourhood::King),
+            "knight" => Some(Neighbourhood::Knight),
+            "manhattan" => Some(Neighbourhood::Manhattan),
+            "cross" => Some(Neighbourhood::Cross),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Neighbourhood {
+        match self {
+            Neighbourhood::King => Neighbourhood::Knight,
+            Neighbourhood::Knight => Neighbourhood::Manhattan,
+            Neighbourhood::Manhattan => Neighbourhood::Cross,
+            Neighbourhood::Cross => Neighbourhood::King,
+        }
+    }
+
+    fn offsets(self) -> &'static [(i32, i32)] {
+        match self {
+            Neighbourhood::King => &KING,
+            Neighbourhood::Knight => &KNIGHT,
+            Neighbourhood::Manhattan => &MANHATTAN,
+            Neighbourhood::Cross => &CROSS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Topology {
+    #[default]
+    Square,
+    Hex,
+    Torus,
+}
+
+impl Topology {
+    pub fn name(self) -> &'static str {
+        match self {
+            Topology::Square => "Square",
+            Topology::Hex => "Hex",
+            Topology::Torus => "Torus",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Topology> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Topology::Square),
+            "hex" => Some(Topology::Hex),
+            "torus" => Some(Topology::Torus),
+            _ => None,
+        }
+    }
+
+    // The one after this, for switching through them from a single button.
+    pub fn next(self) -> Topology {
+        match self {
+            Topology::Square => Topology::Hex,
+            Topology::Hex => Topology::Torus,
+            Topology::Torus => Topology::Square,
+        }
+    }
+
+    fn offsets(self, neighbourhood: Neighbourhood, y: u16) -> &'static [(i32, i32)] {
+        match self {
+            Topology::Square | Topology::Torus => neighbourhood.offsets(),
+            Topology::Hex if !y.is_multiple_of(2) => &HEX_ODD_ROW,
+            Topology::Hex => &HEX_EVEN_ROW,
+        }
+    }
+
+    pub fn wraps(self) -> bool {
+        self == Topology::Torus
+    }
+
+    // What `neighbourhood` comes down to on this grid.
+    pub fn uses(self, neighbourhood: Neighbourhood) -> Neighbourhood {
+        match self {
+            Topology::Hex => Neighbourhood::King,
+            _ => neighbourhood,
+        }
+    }
+
+    // Most neighbours a cell can have, the first click keeps all of them free of mines.
+    pub fn neighbour_count(self, neighbourhood: Neighbourhood) -> usize {
+        self.offsets(neighbourhood, 1).len()
+    }
+
+    pub fn neighbours(self, neighbourhood: Neighbourhood, x: u16, y: u16, width: u16, height: u16) -> Vec<(u16, u16)> {
+        let offsets = self.offsets(neighbourhood, y);
+        let mut result = Vec::with_capacity(offsets.len());
+        for &(dx, dy) in offsets {
+            let mut nx = x as i32 + dx;
+            let mut ny = y as i32 + dy;
+            if self.wraps() {
+                nx = (nx - 1).rem_euclid(width as i32) + 1;
+                ny = (ny - 1).rem_euclid(height as i32) + 1;
+                // on boards narrower than the neighbourhood the way round and the way across
+                // meet in the same cell
+                if (nx as u16, ny as u16) == (x, y) || result.contains(&(nx as u16, ny as u16)) {
+                    continue;
+                }
+            }
+            if nx >= 1 && ny >= 1 && nx <= width as i32 && ny <= height as i32 {
+                result.push((nx as u16, ny as u16));
+            }
+        }
+        result
+    }
+
+    // How far row `y` is pushed right, in tiles.
+    pub fn row_shift(self, y: u16) -> f32 {
+        match self {
+            Topology::Hex if y.is_multiple_of(2) => 0.5,
+            _ => 0.0,
+        }
+    }
+
+    // Tiles drawn around the board on every side.
+    pub fn margin(self) -> f32 {
+        if self.wraps() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    // Width and height of the drawn board including the margin, in tiles.
+    pub fn extent(self, width: u16, height: u16) -> (f32, f32) {
+        match self {
+            Topology::Square => (width as f32, height as f32),
+            Topology::Torus => (width as f32 + 2.0 * self.margin(), height as f32 + 2.0 * self.margin()),
+            Topology::Hex if height > 1 => (width as f32 + 0.5, height as f32),
+            Topology::Hex => (width as f32, height as f32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut cells: Vec<(u16, u16)>) -> Vec<(u16, u16)> {
+        cells.sort();
+        cells
+    }
+
+    // a touches b whenever b touches a
+    fn symmetric(topology: Topology, neighbourhood: Neighbourhood, width: u16, height: u16) -> bool {
+        (1..=height).all(|y| {
+            (1..=width).all(|x| {
+                topology
+                    .neighbours(neighbourhood, x, y, width, height)
+                    .into_iter()
+                    .all(|(nx, ny)| topology.neighbours(neighbourhood, nx, ny, width, height).contains(&(x, y)))
+            })
+        })
+    }
+
+    #[test]
+    fn hex_rows_touch_six_cells() {
+        let king = Neighbourhood::King;
+        // even rows are pushed right, so they reach right above and below
+        assert_eq!(sorted(Topology::Hex.neighbours(king, 2, 2, 3, 3)), vec![(1, 2), (2, 1), (2, 3), (3, 1), (3, 2), (3, 3)]);
+        assert_eq!(sorted(Topology::Hex.neighbours(king, 2, 3, 3, 3)), vec![(1, 2), (1, 3), (2, 2), (3, 3)]);
+        assert!(symmetric(Topology::Hex, king, 4, 5));
+        assert_eq!((Topology::Hex.row_shift(1), Topology::Hex.row_shift(2)), (0.0, 0.5));
+        assert_eq!(Topology::Hex.extent(9, 9), (9.5, 9.0));
+    }
+
+    #[test]
+    fn torus_edges_wrap_around() {
+        let king = Neighbourhood::King;
+        let corner = sorted(Topology::Torus.neighbours(king, 1, 1, 4, 4));
+        assert_eq!(corner, vec![(1, 2), (1, 4), (2, 1), (2, 2), (2, 4), (4, 1), (4, 2), (4, 4)]);
+        // the way round and the way across are the same cell on a 2x2 board
+        assert_eq!(sorted(Topology::Torus.neighbours(king, 1, 1, 2, 2)), vec![(1, 2), (2, 1), (2, 2)]);
+        assert!(symmetric(Topology::Torus, king, 3, 4));
+        assert_eq!(Topology::Torus.extent(9, 9), (11.0, 11.0));
+    }
+
+    #[test]
+    fn neighbourhoods_pick_their_cells() {
+        let square = Topology::Square;
+        assert_eq!(sorted(square.neighbours(Neighbourhood::Knight, 1, 1, 5, 5)), vec![(2, 3), (3, 2)]);
+        assert_eq!(square.neighbours(Neighbourhood::Knight, 3, 3, 5, 5).len(), 8);
+        assert!(!square.neighbours(Neighbourhood::Knight, 3, 3, 5, 5).contains(&(3, 2)));
+        let manhattan = square.neighbours(Neighbourhood::Manhattan, 3, 3, 5, 5);
+        assert_eq!(manhattan.len(), 12);
+        assert!(manhattan.contains(&(3, 1)) && manhattan.contains(&(2, 2)) && !manhattan.contains(&(1, 1)));
+        let cross = square.neighbours(Neighbourhood::Cross, 3, 3, 5, 5);
+        assert_eq!(sorted(cross), vec![(1, 3), (2, 3), (3, 1), (3, 2), (3, 4), (3, 5), (4, 3), (5, 3)]);
+        for neighbourhood in [Neighbourhood::King, Neighbourhood::Knight, Neighbourhood::Manhattan, Neighbourhood::Cross] {
+            assert!(symmetric(square, neighbourhood, 5, 4));
+            assert!(symmetric(Topology::Torus, neighbourhood, 5, 4));
+            assert_eq!(Neighbourhood::from_name(neighbourhood.name()), Some(neighbourhood));
+        }
+    }
+
+    #[test]
+    fn hex_keeps_its_own_neighbours() {
+        assert_eq!(Topology::Hex.uses(Neighbourhood::Knight), Neighbourhood::King);
+        assert_eq!(Topology::Hex.neighbour_count(Neighbourhood::Manhattan), 6);
+        assert_eq!(Topology::Square.neighbour_count(Neighbourhood::Manhattan), 12);
+        assert_eq!(Topology::Torus.uses(Neighbourhood::Cross), Neighbourhood::Cross);
+    }
+}
@@ -0,0 +1,210 @@
+// Playing on a touch screen.
+//
+// A tap reveals (chords on an uncovered number) and holding a finger still flags. The button in
+// the bottom right corner, shown once the screen gets touched, swaps the two so taps flag.
+// Pinching with two fingers zooms in, dragging pans.
+
+use bevy::{prelude::*, render::camera::OrthographicProjection, window::PrimaryWindow};
+
+use crate::{
+    custom::{dialog_closed, PANEL_COLOR}, hud::GameTimer, play, replay::{ActionKind, PlayerAction}, start_game,
+    view::{pan, zoom_at}, world_size, BoardInput, CurrentBoard, Fonts, GameState, MapInfo, Safe, TileIndex,
+};
+
+// seconds a finger has to stay down for a long press
+const LONG_PRESS: f32 = 0.45;
+// pixels a finger may wander and still count as a tap
+const TAP_SLOP: f32 = 12.0;
+
+#[derive(Resource, Default)]
+pub struct TouchMode {
+    pub flag: bool,
+}
+
+// The single finger that may still end up as a tap or a long press.
+#[derive(Resource, Default)]
+struct Gesture {
+    finger: Option<u64>,
+    started: f32,
+    // moved, long pressed, landed on a button or got joined by another finger
+    spent: bool,
+}
+
+#[derive(Component)]
+struct ModeButton;
+
+#[derive(Component)]
+struct ModeLabel;
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchMode>()
+            .init_resource::<Gesture>()
+            .add_system(spawn_mode_button.in_schedule(OnExit(GameState::AssetLoading)))
+            .add_system(mode_button)
+            .add_system(touch_board.in_set(BoardInput))
+            .add_system(touch_view.run_if(dialog_closed));
+    }
+}
+
+fn mode_label(mode: &TouchMode) -> &'static str {
+    if mode.flag {
+        "Tap: flag"
+    } else {
+        "Tap: dig"
+    }
+}
+
+fn spawn_mode_button(mut commands: Commands, fonts: Res<Fonts>, mode: Res<TouchMode>) {
+    commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect { right: Val::Px(4.0), bottom: Val::Px(4.0), ..default() },
+                    size: Size::new(Val::Px(80.0), Val::Px(32.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: PANEL_COLOR.into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ModeButton,
+            Name::new("Touch mode"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    mode_label(&mode),
+                    TextStyle { font: fonts.main.clone(), font_size: 14.0, color: Color::WHITE },
+                ),
+                ModeLabel,
+            ));
+        });
+}
+
+fn mode_button(
+    touches: Res<Touches>,
+    mut mode: ResMut<TouchMode>,
+    mut button: Query<(&Interaction, &mut Visibility), With<ModeButton>>,
+    mut label: Query<&mut Text, With<ModeLabel>>,
+) {
+    let Ok((interaction, mut visibility)) = button.get_single_mut() else { return };
+    // mouse players never see it
+    if touches.any_just_pressed() && *visibility == Visibility::Hidden {
+        *visibility = Visibility::Inherited;
+        return;
+    }
+    if *visibility != Visibility::Hidden && *interaction == Interaction::Clicked && touches.any_just_pressed() {
+        mode.flag = !mode.flag;
+        label.single_mut().sections[0].value = mode_label(&mode).to_string();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn touch_board(
+    touches: Res<Touches>,
+    time: Res<Time>,
+    mode: Res<TouchMode>,
+    mut gesture: ResMut<Gesture>,
+    state: Res<State<GameState>>,
+    ui: Query<&Interaction>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    index: Res<TileIndex>,
+    mut board: ResMut<CurrentBoard>,
+    mut safe: ResMut<Safe>,
+    mut timer: ResMut<GameTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    let now = time.elapsed_seconds();
+    for touch in touches.iter_just_pressed() {
+        if gesture.finger.is_none() {
+            let on_button = ui.iter().any(|interaction| *interaction == Interaction::Clicked);
+            *gesture = Gesture { finger: Some(touch.id()), started: now, spent: on_button };
+        } else {
+            gesture.spent = true;
+        }
+    }
+    let Some(finger) = gesture.finger else { return };
+
+    let flag = if let Some(touch) = touches.get_pressed(finger) {
+        if touches.iter().count() > 1 || touch.distance().length() > TAP_SLOP {
+            gesture.spent = true;
+        }
+        if gesture.spent || now - gesture.started < LONG_PRESS {
+            return;
+        }
+        gesture.spent = true;
+        !mode.flag
+    } else {
+        let tapped = touches.just_released(finger) && !gesture.spent;
+        gesture.finger = None;
+        if !tapped {
+            return;
+        }
+        mode.flag
+    };
+
+    // touch positions count from the top left, viewports from the bottom left
+    let Some(touch) = touches.get_pressed(finger).or_else(|| touches.get_released(finger)) else { return };
+    let window = window.single();
+    let (camera, camera_transform) = camera.single();
+    let position = Vec2::new(touch.position().x, window.height() - touch.position().y);
+    let world = camera.viewport_to_world_2d(camera_transform, position);
+    let Some((x, y)) = world.and_then(|world| index.cell_at(world)) else { return };
+
+    match state.0 {
+        GameState::SafeClick if !flag => {
+            start_game((x, y), &mut safe, &mut timer, &mut next_state, &mut actions);
+        }
+        GameState::InGame => {
+            let kind = if flag {
+                ActionKind::Flag
+            } else if board.cell(x, y).is_some_and(|cell| cell.covered) {
+                ActionKind::Reveal
+            } else {
+                ActionKind::Chord
+            };
+            play(&mut board, kind, x, y, &mut actions);
+        }
+        _ => {}
+    }
+}
+
+fn touch_view(
+    touches: Res<Touches>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    map_info: Res<MapInfo>,
+    mut camera: Query<(&Camera, &GlobalTransform, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let fingers: Vec<_> = touches.iter().collect();
+    let (moved, zoom, middle) = match fingers[..] {
+        [one] if one.distance().length() > TAP_SLOP => (one.delta(), 1.0, one.position()),
+        [one, two, ..] => {
+            let before = one.previous_position().distance(two.previous_position());
+            let after = one.position().distance(two.position());
+            let zoom = if after > 0.0 { before / after } else { 1.0 };
+            ((one.delta() + two.delta()) / 2.0, zoom, (one.position() + two.position()) / 2.0)
+        }
+        _ => return,
+    };
+    if moved == Vec2::ZERO && zoom == 1.0 {
+        return;
+    }
+
+    let window = window.single();
+    let world = world_size(&map_info);
+    let (camera, camera_transform, mut transform, mut projection) = camera.single_mut();
+    // touch positions count from the top left, viewports from the bottom left
+    let middle = Vec2::new(middle.x, window.height() - middle.y);
+    if let Some(anchor) = camera.viewport_to_world_2d(camera_transform, middle) {
+        zoom_at(window, world, &mut transform, &mut projection, zoom, anchor);
+    }
+    pan(window, world, &mut transform, &mut projection, Vec2::new(moved.x, -moved.y));
+}
@@ -0,0 +1,174 @@
+// Fitting the board into the window, zooming and panning.
+//
+// The board lives at a fixed size in the world and the camera scales it to fit the window
+// whenever the window is resized or a new game starts. The mouse wheel zooms towards the
+// pointer, up to MAX_ZOOM times closer, and dragging with the middle button pans. The menu bar
+// covers the top of the window, so the board is fitted into what is left below it.
+
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::camera::OrthographicProjection,
+    window::{PrimaryWindow, WindowResized},
+};
+
+use crate::{custom::dialog_closed, cursor_world, menu::MENU_HEIGHT, world_size, GameState, MapInfo};
+
+pub const MAX_ZOOM: f32 = 4.0;
+// scale change per wheel notch
+const WHEEL_STEP: f32 = 1.15;
+// pixel scrolling (touchpads, browsers) counts this many pixels as a notch
+const PIXELS_PER_NOTCH: f32 = 50.0;
+// pixels the pointer may move with the middle button down and still chord on release
+const DRAG_SLOP: f32 = 4.0;
+
+// Middle button drag in progress, `panned` tells the board input not to chord on release.
+#[derive(Resource, Default)]
+pub struct MousePan {
+    last: Option<Vec2>,
+    travelled: f32,
+    pub panned: bool,
+}
+
+pub struct ViewPlugin;
+
+impl Plugin for ViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MousePan>()
+            .add_system(reset_view.in_schedule(OnEnter(GameState::SafeClick)))
+            .add_system(refit_on_resize)
+            .add_systems((wheel_zoom, drag_pan).distributive_run_if(dialog_closed));
+    }
+}
+
+// The part of the window below the menu bar, in pixels.
+fn board_area(window: &Window) -> Vec2 {
+    Vec2::new(window.width(), (window.height() - MENU_HEIGHT).max(1.0))
+}
+
+fn fit_scale(window: &Window, world: Vec2) -> f32 {
+    (world / board_area(window)).max_element()
+}
+
+// From the middle of the board area to the middle of the window, which is where the camera looks.
+fn bar_offset(projection: &OrthographicProjection) -> Vec2 {
+    Vec2::new(0.0, MENU_HEIGHT / 2.0 * projection.scale)
+}
+
+// Keeps the zoom between the whole board and MAX_ZOOM, and the view from leaving the board.
+pub fn clamp_view(window: &Window, world: Vec2, transform: &mut Transform, projection: &mut OrthographicProjection) {
+    let fit = fit_scale(window, world);
+    projection.scale = projection.scale.clamp(fit / MAX_ZOOM, fit);
+    let half = board_area(window) * projection.scale / 2.0;
+    let center = world / 2.0;
+    let offset = bar_offset(projection);
+    let position = (transform.translation.truncate() - offset).clamp(half.min(center), (world - half).max(center));
+    transform.translation = (position + offset).extend(transform.translation.z);
+}
+
+pub fn fit_view(window: &Window, world: Vec2, transform: &mut Transform, projection: &mut OrthographicProjection) {
+    projection.scale = fit_scale(window, world);
+    transform.translation = (world / 2.0 + bar_offset(projection)).extend(transform.translation.z);
+}
+
+// Scales by `factor` keeping the world point `anchor` where it is on screen.
+pub fn zoom_at(
+    window: &Window,
+    world: Vec2,
+    transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+    factor: f32,
+    anchor: Vec2,
+) {
+    let old = projection.scale;
+    projection.scale *= factor;
+    clamp_view(window, world, transform, projection);
+    let position = anchor + (transform.translation.truncate() - anchor) * projection.scale / old;
+    transform.translation = position.extend(transform.translation.z);
+    clamp_view(window, world, transform, projection);
+}
+
+// Moves the view by `delta` screen pixels (y up), the board follows the pointer.
+pub fn pan(window: &Window, world: Vec2, transform: &mut Transform, projection: &mut OrthographicProjection, delta: Vec2) {
+    transform.translation -= (delta * projection.scale).extend(0.0);
+    clamp_view(window, world, transform, projection);
+}
+
+fn reset_view(
+    window: Query<&Window, With<PrimaryWindow>>,
+    map_info: Res<MapInfo>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection)>,
+) {
+    let (mut transform, mut projection) = camera.single_mut();
+    fit_view(window.single(), world_size(&map_info), &mut transform, &mut projection);
+}
+
+fn refit_on_resize(
+    mut resized: EventReader<WindowResized>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    map_info: Res<MapInfo>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection)>,
+) {
+    if resized.iter().count() == 0 {
+        return;
+    }
+    let (mut transform, mut projection) = camera.single_mut();
+    fit_view(window.single(), world_size(&map_info), &mut transform, &mut projection);
+}
+
+fn wheel_zoom(
+    mut wheel: EventReader<MouseWheel>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    map_info: Res<MapInfo>,
+    mut camera: Query<(&Camera, &GlobalTransform, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let notches: f32 = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+        })
+        .sum();
+    if notches == 0.0 {
+        return;
+    }
+    let window = window.single();
+    let (camera, camera_transform, mut transform, mut projection) = camera.single_mut();
+    let anchor = cursor_world(window, camera, camera_transform).unwrap_or(transform.translation.truncate());
+    let factor = WHEEL_STEP.powf(-notches);
+    zoom_at(window, world_size(&map_info), &mut transform, &mut projection, factor, anchor);
+}
+
+fn drag_pan(
+    buttons: Res<Input<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    map_info: Res<MapInfo>,
+    mut drag: ResMut<MousePan>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection)>,
+) {
+    let window = window.single();
+    let position = window.cursor_position();
+    if buttons.just_pressed(MouseButton::Middle) {
+        *drag = MousePan { last: position, travelled: 0.0, panned: false };
+        return;
+    }
+    if !buttons.pressed(MouseButton::Middle) {
+        drag.last = None;
+        return;
+    }
+    let (Some(last), Some(position)) = (drag.last, position) else {
+        drag.last = position;
+        return;
+    };
+    let delta = position - last;
+    if delta == Vec2::ZERO {
+        return;
+    }
+    drag.last = Some(position);
+    drag.travelled += delta.length();
+    if drag.travelled > DRAG_SLOP {
+        drag.panned = true;
+    }
+    let (mut transform, mut projection) = camera.single_mut();
+    pan(window, world_size(&map_info), &mut transform, &mut projection, delta);
+}
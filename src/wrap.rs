@@ -0,0 +1,77 @@
+// Edge hints for boards that wrap around.
+//
+// A torus board gets a one tile border of faded copies: the column past the right edge shows
+// the leftmost column, the row above the top shows the bottom row, the corners show the opposite
+// corners. They follow the real tiles as they get opened and flagged, but can't be clicked.
+
+use bevy::prelude::*;
+use bevy_despawn_with::DespawnAllCommandsExt;
+
+use crate::{spawn_tiles, CurrentBoard, GameState, MapInfo, Tile, TileIndex, TileSprites, TILE_SIZE};
+
+const EDGE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.4);
+
+// Faded copy of the tile at x/y.
+#[derive(Component)]
+struct EdgeCopy {
+    x: u16,
+    y: u16,
+}
+
+pub struct WrapPlugin;
+
+impl Plugin for WrapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_edges.after(spawn_tiles).in_schedule(OnEnter(GameState::SafeClick)))
+            .add_system(sync_edges);
+    }
+}
+
+fn spawn_edges(
+    mut commands: Commands,
+    tile_sprites: Res<TileSprites>,
+    map_info: Res<MapInfo>,
+    board: Res<CurrentBoard>,
+    index: Res<TileIndex>,
+) {
+    commands.despawn_all::<With<EdgeCopy>>();
+    if !map_info.topology.wraps() {
+        return;
+    }
+    let (width, height) = (board.width() as i32, board.height() as i32);
+    for gy in 0..=height + 1 {
+        for gx in 0..=width + 1 {
+            if (1..=width).contains(&gx) && (1..=height).contains(&gy) {
+                continue;
+            }
+            let x = ((gx - 1).rem_euclid(width) + 1) as u16;
+            let y = ((gy - 1).rem_euclid(height) + 1) as u16;
+            // the copy sits a whole board away from the tile it shows
+            let across = Vec2::new((gx - x as i32) as f32, (y as i32 - gy) as f32) * TILE_SIZE;
+            commands.spawn((
+                EdgeCopy { x, y },
+                SpriteBundle {
+                    sprite: Sprite { color: EDGE_COLOR, ..default() },
+                    texture: tile_sprites.unknown.clone(),
+                    transform: Transform::from_translation((index.center(x, y) + across).extend(0.0))
+                        .with_scale(Vec3::new(2.0, 2.0, 0.0)),
+                    ..default()
+                },
+                Name::new(format!("Edge copy ({}, {})", x, y)),
+            ));
+        }
+    }
+}
+
+fn sync_edges(
+    index: Res<TileIndex>,
+    tiles: Query<&Handle<Image>, With<Tile>>,
+    mut copies: Query<(&EdgeCopy, &mut Handle<Image>), Without<Tile>>,
+) {
+    for (copy, mut image) in copies.iter_mut() {
+        let Some(tile) = index.get(copy.x, copy.y).and_then(|entity| tiles.get(entity).ok()) else { continue };
+        if *image != *tile {
+            *image = tile.clone();
+        }
+    }
+}